@@ -0,0 +1,596 @@
+use crate::pixel::Pixel;
+
+// linear light value (0..=65535) of every sRGB value, built at compile time
+pub const SRGB_TO_LINEAR: [u16; 256] = srgb_to_linear_table();
+// smallest linear light value (0..=65535) that rounds to each sRGB value, index 0 is unused
+const LINEAR_TO_SRGB: [u16; 256] = linear_to_srgb_table();
+// linear light value (0.0..=1.0) of every sRGB value, built at compile time
+pub const SRGB_TO_LINEAR_F32: [f32; 256] = srgb_to_linear_f32_table();
+// smallest linear light value (0.0..=1.0) that rounds to each sRGB value, index 0 is unused
+const LINEAR_F32_TO_SRGB: [f32; 256] = linear_f32_to_srgb_table();
+
+/// How colour values are multiplied by alpha for premultiplied alpha.
+///
+/// QOI images always store straight alpha so the colour values must be multiplied by alpha when decoding and divided
+/// by alpha when encoding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiPremultiply {
+    /// Multiplies the stored colour values by alpha directly.
+    Stored,
+    /// Converts sRGB colour values to linear light before multiplying by alpha and converts them back afterwards.
+    ///
+    /// Images with a colorspace value of `1` (all channels linear) are already linear so they are multiplied directly.
+    LinearLight,
+}
+
+/// How values with more than 8 bits of precision are reduced to the 8 bit values stored in QOI images.
+///
+/// Values are first scaled so that the largest input value becomes `255.0`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiReduction {
+    /// Discards the fractional part of the scaled value.
+    Truncate,
+    /// Rounds the scaled value to the nearest whole number. This is the default.
+    Round,
+    /// Adds a threshold from an `8x8` Bayer matrix based on the pixel position before discarding the fractional part.
+    ///
+    /// Neighbouring pixels of a smooth gradient are spread between the two closest values which avoids visible bands
+    /// at the cost of larger QOI images.
+    OrderedDither,
+}
+
+/// The background fully or partially transparent pixels are composited over when flattening alpha.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiBackground {
+    /// A single RGB colour.
+    Solid([u8; 3]),
+    /// A checkerboard of two RGB colours starting with the light colour in the top left corner.
+    /// The size is the width and height in pixels of each square, a size of `0` is treated as `1`.
+    Checkerboard {
+        /// The width and height in pixels of each square.
+        size: u32,
+        /// The colour of the top left square.
+        light: [u8; 3],
+        /// The colour of the other squares.
+        dark: [u8; 3],
+    },
+}
+
+/// The matrix converting between RGB and YCbCr (YUV) values.
+///
+/// Both use limited (video) range with luma values of `16..=235` and chroma values of `16..=240`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiYuvMatrix {
+    /// ITU-R BT.601 used by standard definition video and most JPEG based cameras.
+    Bt601,
+    /// ITU-R BT.709 used by high definition video.
+    Bt709,
+}
+
+/// The memory layout of YCbCr (YUV) frames.
+///
+/// Chroma values are shared by `2` horizontally neighbouring pixels and with 4:2:0 subsampling also by `2` vertically
+/// neighbouring pixels. Images with an odd width or height have a last chroma column or row for a single pixel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiYuvLayout {
+    /// 4:2:0 with a plane of luma values followed by a plane of Cb (U) values and a plane of Cr (V) values.
+    I420,
+    /// 4:2:0 with a plane of luma values followed by a plane of interleaved Cb (U) and Cr (V) values.
+    Nv12,
+    /// 4:2:2 with every `2` pixels packed into `4` bytes in the order Y0, Cb (U), Y1, Cr (V).
+    Yuyv,
+}
+
+impl QoiYuvLayout {
+    // size in bytes of a whole frame
+    pub(crate) const fn frame_size(self, width: u32, height: u32) -> u64 {
+        let (width, height) = (width as u64, height as u64);
+        match self {
+            Self::I420 | Self::Nv12 => width * height + width.div_ceil(2) * height.div_ceil(2) * 2,
+            Self::Yuyv => width.div_ceil(2) * 4 * height,
+        }
+    }
+    // rows sharing chroma values
+    pub(crate) const fn band_rows(self) -> u32 {
+        match self {
+            Self::I420 | Self::Nv12 => 2,
+            Self::Yuyv => 1,
+        }
+    }
+}
+
+impl QoiBackground {
+    // the background colour at the pixel position
+    #[inline]
+    pub(crate) const fn color_at(self, x: u32, y: u32) -> [u8; 3] {
+        match self {
+            Self::Solid(color) => color,
+            Self::Checkerboard {size, light, dark} => {
+                let size = if size == 0 {1} else {size};
+                if (x / size + y / size) % 2 == 0 {light} else {dark}
+            },
+        }
+    }
+    #[inline]
+    pub(crate) const fn is_checkerboard(self) -> bool {
+        matches!(self, Self::Checkerboard {..})
+    }
+}
+
+// the most colour keys an encoder can hold without allocating
+pub const MAX_COLOR_KEYS: usize = 8;
+
+// colours replaced by fully transparent pixels when encoding
+#[derive(Clone, Copy)]
+pub struct ColorKeys {
+    keys: [[u8; 3]; MAX_COLOR_KEYS],
+    amount: usize,
+    tolerance: u8, // largest difference of any colour value still matching a key
+}
+
+impl ColorKeys {
+    pub const fn new(keys: &[[u8; 3]], tolerance: u8) -> Option<Self> {
+        if keys.is_empty() || keys.len() > MAX_COLOR_KEYS {return None;}
+        let mut stored = [[0; 3]; MAX_COLOR_KEYS];
+        let mut index = 0;
+        while index < keys.len() {
+            stored[index] = keys[index];
+            index += 1;
+        }
+        Some(Self {keys: stored, amount: keys.len(), tolerance})
+    }
+    // whether the colour values of the pixel are within the tolerance of any key
+    #[inline]
+    pub const fn matches(&self, pixel: Pixel) -> bool {
+        let mut index = 0;
+        while index < self.amount {
+            let key = self.keys[index];
+            if pixel.red.abs_diff(key[0]) <= self.tolerance
+                && pixel.green.abs_diff(key[1]) <= self.tolerance
+                && pixel.blue.abs_diff(key[2]) <= self.tolerance {return true;}
+            index += 1;
+        }
+        false
+    }
+}
+
+// 8x8 Bayer matrix, each value is the order in which a threshold is crossed (0..=63)
+const BAYER: [[u8; 8]; 8] = [[0, 32, 8, 40, 2, 34, 10, 42],
+                             [48, 16, 56, 24, 50, 18, 58, 26],
+                             [12, 44, 4, 36, 14, 46, 6, 38],
+                             [60, 28, 52, 20, 62, 30, 54, 22],
+                             [3, 35, 11, 43, 1, 33, 9, 41],
+                             [51, 19, 59, 27, 49, 17, 57, 25],
+                             [15, 47, 7, 39, 13, 45, 5, 37],
+                             [63, 31, 55, 23, 61, 29, 53, 21]];
+
+impl QoiPremultiply {
+    // whether the colour values need converting to linear light for the colorspace value of the image
+    #[inline]
+    pub(crate) const fn is_linear_light(self, colorspace: u8) -> bool {
+        matches!(self, Self::LinearLight) && colorspace == 0
+    }
+}
+
+// returns the sRGB value (0..=255) closest to the linear light value (0..=65535)
+pub const fn linear_to_srgb(value: u16) -> u8 {
+    let mut low: usize = 0;
+    let mut high = 255;
+    while low < high { // binary search for the largest sRGB value whose threshold is not above the linear value
+        let middle = (low + high).div_ceil(2);
+        if LINEAR_TO_SRGB[middle] <= value {low = middle;} else {high = middle - 1;}
+    }
+    #[allow(clippy::cast_possible_truncation)] // index guaranteed to be 0..=255 so cannot truncate when casting to u8
+    let srgb = low as u8;
+    srgb
+}
+
+// returns the sRGB value (0..=255) closest to the linear light value (0.0..=1.0). NaN and values outside are clamped
+pub const fn linear_f32_to_srgb(value: f32) -> u8 {
+    if value.is_nan() {return 0;}
+    let mut low: usize = 0;
+    let mut high = 255;
+    while low < high { // binary search for the largest sRGB value whose threshold is not above the linear value
+        let middle = (low + high).div_ceil(2);
+        if LINEAR_F32_TO_SRGB[middle] <= value {low = middle;} else {high = middle - 1;}
+    }
+    #[allow(clippy::cast_possible_truncation)] // index guaranteed to be 0..=255 so cannot truncate when casting to u8
+    let srgb = low as u8;
+    srgb
+}
+
+// rounds a 16 bit value (0..=65535) to the nearest 8 bit value (0..=255)
+#[allow(clippy::cast_possible_truncation)] // result guaranteed to be 0..=255 so cannot truncate
+#[inline]
+pub const fn u16_to_u8(value: u16) -> u8 {
+    ((value as u32 * 255 + 32767) / 65535) as u8
+}
+
+// rounds a float value (0.0..=1.0) to the nearest 8 bit value (0..=255). NaN and values outside are clamped
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // value clamped so cannot truncate or lose sign
+#[inline]
+pub const fn f32_to_u8(value: f32) -> u8 {
+    if value.is_nan() || value <= 0.0 {return 0;}
+    if value >= 1.0 {return 255;}
+    (value * 255.0 + 0.5) as u8
+}
+
+// reduces a 16 bit value (0..=65535) to an 8 bit value (0..=255). x and y are only used for dithering
+#[allow(clippy::cast_possible_truncation)] // result guaranteed to be 0..=255 so cannot truncate
+pub const fn reduce_u16(value: u16, reduction: QoiReduction, x: u32, y: u32) -> u8 {
+    let value = value as u64 * 255;
+    match reduction {
+        QoiReduction::Truncate => (value / 65535) as u8,
+        QoiReduction::Round => ((value + 32767) / 65535) as u8,
+        QoiReduction::OrderedDither => { // threshold is (2 * bayer + 1) / 128 so it is never 0.0 or 1.0
+            let threshold = (2 * BAYER[(y % 8) as usize][(x % 8) as usize] as u64 + 1) * 65535;
+            ((value * 128 + threshold) / (65535 * 128)) as u8
+        },
+    }
+}
+
+// reduces a float value (0.0..=1.0) to an 8 bit value (0..=255). NaN and values outside are clamped
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // value clamped so cannot truncate or lose sign
+pub const fn reduce_f32(value: f32, reduction: QoiReduction, x: u32, y: u32) -> u8 {
+    if value.is_nan() || value <= 0.0 {return 0;}
+    if value >= 1.0 {return 255;}
+    let value = value * 255.0;
+    match reduction {
+        QoiReduction::Truncate => value as u8,
+        QoiReduction::Round => (value + 0.5) as u8,
+        QoiReduction::OrderedDither => {
+            let threshold = (2 * BAYER[(y % 8) as usize][(x % 8) as usize] + 1) as f32 / 128.0;
+            (value + threshold) as u8
+        },
+    }
+}
+
+// converts the colour values of the pixel to luma and chroma values
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // values guaranteed to be within 16..=240
+pub const fn rgb_to_yuv(pixel: Pixel, matrix: QoiYuvMatrix) -> [u8; 3] {
+    let (red, green, blue) = (pixel.red as i32, pixel.green as i32, pixel.blue as i32);
+    let (y, u, v) = match matrix {
+        QoiYuvMatrix::Bt601 => (66 * red + 129 * green + 25 * blue,
+                                -38 * red - 74 * green + 112 * blue,
+                                112 * red - 94 * green - 18 * blue),
+        QoiYuvMatrix::Bt709 => (47 * red + 157 * green + 16 * blue,
+                                -26 * red - 87 * green + 112 * blue,
+                                112 * red - 102 * green - 10 * blue),
+    };
+    [(((y + 128) >> 8) + 16) as u8, (((u + 128) >> 8) + 128) as u8, (((v + 128) >> 8) + 128) as u8]
+}
+
+// converts luma and chroma values to an opaque pixel
+pub const fn yuv_to_rgb(y: u8, u: u8, v: u8, matrix: QoiYuvMatrix) -> Pixel {
+    let (luma, u, v) = (298 * (y as i32 - 16) + 128, u as i32 - 128, v as i32 - 128);
+    let (red, green, blue) = match matrix {
+        QoiYuvMatrix::Bt601 => (luma + 409 * v, luma - 100 * u - 208 * v, luma + 516 * u),
+        QoiYuvMatrix::Bt709 => (luma + 459 * v, luma - 55 * u - 136 * v, luma + 541 * u),
+    };
+    Pixel::new(clamp(red >> 8), clamp(green >> 8), clamp(blue >> 8), 255)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // value is clamped to 0..=255 before casting
+#[inline]
+const fn clamp(value: i32) -> u8 {
+    if value < 0 {0} else if value > 255 {255} else {value as u8}
+}
+
+// composites the pixel over the background colour resulting in an opaque pixel
+pub const fn flatten(pixel: Pixel, background: [u8; 3], linear_light: bool) -> Pixel {
+    if pixel.alpha == 255 {return pixel;}
+    let alpha = pixel.alpha as u32;
+    Pixel::new(blend(pixel.red, background[0], alpha, linear_light),
+               blend(pixel.green, background[1], alpha, linear_light),
+               blend(pixel.blue, background[2], alpha, linear_light),
+               255)
+}
+
+#[allow(clippy::cast_possible_truncation)] // result guaranteed to be within the range of the values so cannot truncate
+#[inline]
+const fn blend(value: u8, background: u8, alpha: u32, linear_light: bool) -> u8 {
+    if linear_light {
+        let value = SRGB_TO_LINEAR[value as usize] as u32;
+        let background = SRGB_TO_LINEAR[background as usize] as u32;
+        linear_to_srgb(((value * alpha + background * (255 - alpha) + 127) / 255) as u16)
+    } else {
+        ((value as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255) as u8
+    }
+}
+
+// multiplies the colour values of the pixel by its alpha value
+pub const fn premultiply(pixel: Pixel, linear_light: bool) -> Pixel {
+    if pixel.alpha == 255 {return pixel;}
+    let alpha = pixel.alpha as u32;
+    Pixel::new(multiply(pixel.red, alpha, linear_light),
+               multiply(pixel.green, alpha, linear_light),
+               multiply(pixel.blue, alpha, linear_light),
+               pixel.alpha)
+}
+
+// divides the premultiplied colour values of the pixel by its alpha value
+pub const fn unpremultiply(pixel: Pixel, linear_light: bool) -> Pixel {
+    if pixel.alpha == 255 {return pixel;}
+    if pixel.alpha == 0 {return Pixel::new(0, 0, 0, 0);} // colour is lost when fully transparent
+    let alpha = pixel.alpha as u32;
+    Pixel::new(divide(pixel.red, alpha, linear_light),
+               divide(pixel.green, alpha, linear_light),
+               divide(pixel.blue, alpha, linear_light),
+               pixel.alpha)
+}
+
+#[allow(clippy::cast_possible_truncation)] // result guaranteed to be less than or equal to value so cannot truncate
+#[inline]
+const fn multiply(value: u8, alpha: u32, linear_light: bool) -> u8 {
+    if linear_light {
+        linear_to_srgb(((SRGB_TO_LINEAR[value as usize] as u32 * alpha + 127) / 255) as u16)
+    } else {
+        ((value as u32 * alpha + 127) / 255) as u8 // adding 127 rounds to nearest as 255 is odd so never halfway
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)] // result clamped so cannot truncate
+#[inline]
+const fn divide(value: u8, alpha: u32, linear_light: bool) -> u8 {
+    if linear_light {
+        let linear = (SRGB_TO_LINEAR[value as usize] as u32 * 255 + alpha / 2) / alpha;
+        linear_to_srgb(if linear > 65535 {65535} else {linear as u16})
+    } else {
+        let straight = (value as u32 * 255 + alpha / 2) / alpha;
+        if straight > 255 {255} else {straight as u8}
+    }
+}
+
+// converts an sRGB value (0.0..=1.0) to linear light (0.0..=1.0)
+pub const fn srgb_to_linear_f64(value: f64) -> f64 {
+    if value <= 0.040_45 {value / 12.92} else {pow((value + 0.055) / 1.055, 2.4)}
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // values guaranteed to be 0.0..=65535.5
+const fn srgb_to_linear_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut index: u16 = 0;
+    while index < 256 {
+        table[index as usize] = (srgb_to_linear_f64(index as f64 / 255.0) * 65535.0 + 0.5) as u16;
+        index += 1;
+    }
+    table
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // values guaranteed to be 0.0..=65535.0
+const fn linear_to_srgb_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut index: u16 = 1;
+    while index < 256 {
+        let threshold = srgb_to_linear_f64((index as f64 - 0.5) / 255.0) * 65535.0; // halfway to previous sRGB value
+        let rounded_down = threshold as u16;
+        table[index as usize] = if (rounded_down as f64) < threshold {rounded_down + 1} else {rounded_down};
+        index += 1;
+    }
+    table
+}
+
+#[allow(clippy::cast_possible_truncation)] // precision loss is acceptable for f32 tables
+const fn srgb_to_linear_f32_table() -> [f32; 256] {
+    let mut table = [0.0; 256];
+    let mut index: u16 = 0;
+    while index < 256 {
+        table[index as usize] = srgb_to_linear_f64(index as f64 / 255.0) as f32;
+        index += 1;
+    }
+    table
+}
+
+#[allow(clippy::cast_possible_truncation)] // precision loss is acceptable for f32 tables
+const fn linear_f32_to_srgb_table() -> [f32; 256] {
+    let mut table = [0.0; 256];
+    let mut index: u16 = 1;
+    while index < 256 {
+        table[index as usize] = srgb_to_linear_f64((index as f64 - 0.5) / 255.0) as f32; // halfway to previous sRGB value
+        index += 1;
+    }
+    table
+}
+
+// raises a positive value to a power, only accurate enough for building tables
+const fn pow(value: f64, power: f64) -> f64 {
+    exp(power * ln(value))
+}
+
+// natural logarithm of a positive value
+const fn ln(mut value: f64) -> f64 {
+    let mut exponent = 0.0;
+    while value >= 2.0 {value /= 2.0; exponent += 1.0;}
+    while value < 1.0 {value *= 2.0; exponent -= 1.0;}
+    let z = (value - 1.0) / (value + 1.0); // value is 1.0..2.0 so z is 0.0..1/3 and the series converges quickly
+    let z_squared = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    let mut n = 1.0;
+    while n < 60.0 {
+        sum += term / n;
+        term *= z_squared;
+        n += 2.0;
+    }
+    exponent * core::f64::consts::LN_2 + 2.0 * sum
+}
+
+// exponential of a value
+const fn exp(value: f64) -> f64 {
+    let mut halvings = 0;
+    let mut reduced = value;
+    while reduced > 0.5 || reduced < -0.5 {reduced /= 2.0; halvings += 1;} // exp(x) = exp(x / 2)^2
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut n = 1.0;
+    while n < 25.0 {
+        term *= reduced / n;
+        sum += term;
+        n += 1.0;
+    }
+    while halvings != 0 {sum *= sum; halvings -= 1;}
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pixel::Pixel;
+    use super::{
+        f32_to_u8, flatten, linear_f32_to_srgb, linear_to_srgb, premultiply, reduce_f32, reduce_u16, rgb_to_yuv,
+        u16_to_u8, unpremultiply, yuv_to_rgb, ColorKeys, QoiBackground, QoiPremultiply, QoiReduction, QoiYuvLayout,
+        QoiYuvMatrix, MAX_COLOR_KEYS, SRGB_TO_LINEAR, SRGB_TO_LINEAR_F32,
+    };
+    #[test]
+    const fn infallible_srgb_to_linear() {
+        assert!(SRGB_TO_LINEAR[0] == 0);
+        assert!(SRGB_TO_LINEAR[1] == 20);     // 0.000303527 linear
+        assert!(SRGB_TO_LINEAR[128] == 14146); // 0.215860500 linear
+        assert!(SRGB_TO_LINEAR[255] == 65535);
+    }
+    #[test]
+    const fn infallible_linear_to_srgb() {
+        let mut index = 0;
+        while index < 256 {
+            assert!(linear_to_srgb(SRGB_TO_LINEAR[index]) == index as u8);
+            index += 1;
+        }
+        assert!(linear_to_srgb(9) == 0);      // below halfway to sRGB 1
+        assert!(linear_to_srgb(10) == 1);     // above halfway to sRGB 1
+        assert!(linear_to_srgb(32768) == 188); // 0.5 linear
+        assert!(linear_to_srgb(65535) == 255);
+    }
+    #[test]
+    const fn infallible_linear_f32_to_srgb() {
+        let mut index = 0;
+        while index < 256 {
+            assert!(linear_f32_to_srgb(SRGB_TO_LINEAR_F32[index]) == index as u8);
+            index += 1;
+        }
+        assert!(linear_f32_to_srgb(0.5) == 188);
+        assert!(linear_f32_to_srgb(-1.0) == 0);
+        assert!(linear_f32_to_srgb(2.0) == 255);
+        assert!(linear_f32_to_srgb(f32::NAN) == 0);
+        assert!(linear_f32_to_srgb(f32::INFINITY) == 255);
+    }
+    #[test]
+    const fn infallible_u16_to_u8() {
+        assert!(u16_to_u8(0) == 0);
+        assert!(u16_to_u8(128) == 0);
+        assert!(u16_to_u8(129) == 1);
+        assert!(u16_to_u8(257) == 1);
+        assert!(u16_to_u8(65535) == 255);
+    }
+    #[test]
+    const fn infallible_f32_to_u8() {
+        assert!(f32_to_u8(0.5) == 128);
+        assert!(f32_to_u8(0.498) == 127);
+        assert!(f32_to_u8(-0.5) == 0);
+        assert!(f32_to_u8(1.5) == 255);
+        assert!(f32_to_u8(f32::NAN) == 0);
+    }
+    #[test]
+    const fn infallible_reduce_u16() {
+        assert!(reduce_u16(65280, QoiReduction::Truncate, 0, 0) == 254); // 254.00...
+        assert!(reduce_u16(65407, QoiReduction::Truncate, 0, 0) == 254); // 254.50...
+        assert!(reduce_u16(65407, QoiReduction::Round, 0, 0) == 255);
+        assert!(reduce_u16(65535, QoiReduction::Truncate, 0, 0) == 255);
+        assert!(reduce_u16(65535, QoiReduction::OrderedDither, 0, 7) == 255); // largest threshold
+        assert!(reduce_u16(0, QoiReduction::OrderedDither, 0, 7) == 0);
+        let mut above = 0;
+        let mut x = 0;
+        while x < 64 { // 254.25 becomes 255 for a quarter of the positions
+            if reduce_u16(65343, QoiReduction::OrderedDither, x % 8, x / 8) == 255 {above += 1;}
+            x += 1;
+        }
+        assert!(above == 16);
+    }
+    #[test]
+    const fn infallible_reduce_f32() {
+        assert!(reduce_f32(0.999, QoiReduction::Truncate, 0, 0) == 254);
+        assert!(reduce_f32(0.999, QoiReduction::Round, 0, 0) == 255);
+        assert!(reduce_f32(1.5, QoiReduction::Truncate, 0, 0) == 255);
+        assert!(reduce_f32(-0.5, QoiReduction::OrderedDither, 0, 7) == 0);
+        assert!(reduce_f32(f32::NAN, QoiReduction::Round, 0, 0) == 0);
+        assert!(reduce_f32(0.5, QoiReduction::OrderedDither, 0, 0) == 127);  // 127.5 with smallest threshold
+        assert!(reduce_f32(0.5, QoiReduction::OrderedDither, 0, 7) == 128); // 127.5 with largest threshold
+    }
+    #[test]
+    const fn infallible_flatten() {
+        let pixel = flatten(Pixel::new(255, 0, 0, 128), [0, 0, 255], false);
+        assert!(pixel.is_same(Pixel::new(128, 0, 127, 255)));
+        let pixel = flatten(Pixel::new(255, 0, 0, 128), [0, 0, 255], true);
+        assert!(pixel.is_same(Pixel::new(188, 0, 187, 255))); // mixed in linear light
+        let pixel = flatten(Pixel::new(255, 0, 0, 0), [1, 2, 3], true);
+        assert!(pixel.is_same(Pixel::new(1, 2, 3, 255)));
+        let pixel = flatten(Pixel::new(9, 8, 7, 255), [1, 2, 3], true);
+        assert!(pixel.is_same(Pixel::new(9, 8, 7, 255)));
+    }
+    #[test]
+    const fn infallible_color_at() {
+        let background = QoiBackground::Checkerboard {size: 2, light: [255, 255, 255], dark: [0, 0, 0]};
+        assert!(background.color_at(0, 0)[0] == 255 && background.color_at(1, 1)[0] == 255);
+        assert!(background.color_at(2, 0)[0] == 0 && background.color_at(0, 3)[0] == 0);
+        assert!(background.color_at(3, 3)[0] == 255);
+        let background = QoiBackground::Checkerboard {size: 0, light: [255, 255, 255], dark: [0, 0, 0]};
+        assert!(background.color_at(1, 0)[0] == 0);
+        assert!(QoiBackground::Solid([5, 6, 7]).color_at(100, 100)[2] == 7);
+    }
+    #[test]
+    const fn infallible_yuv() {
+        let white = rgb_to_yuv(Pixel::new(255, 255, 255, 255), QoiYuvMatrix::Bt601);
+        assert!(white[0] == 235 && white[1] == 128 && white[2] == 128);
+        let black = rgb_to_yuv(Pixel::new(0, 0, 0, 255), QoiYuvMatrix::Bt709);
+        assert!(black[0] == 16 && black[1] == 128 && black[2] == 128);
+        let red = rgb_to_yuv(Pixel::new(255, 0, 0, 255), QoiYuvMatrix::Bt601);
+        assert!(red[0] == 82 && red[1] == 90 && red[2] == 240);
+        let red = rgb_to_yuv(Pixel::new(255, 0, 0, 255), QoiYuvMatrix::Bt709);
+        assert!(red[0] == 63 && red[1] == 102 && red[2] == 240);
+        assert!(yuv_to_rgb(235, 128, 128, QoiYuvMatrix::Bt709).is_same(Pixel::new(255, 255, 255, 255)));
+        assert!(yuv_to_rgb(16, 128, 128, QoiYuvMatrix::Bt601).is_same(Pixel::new(0, 0, 0, 255)));
+        assert!(yuv_to_rgb(82, 90, 240, QoiYuvMatrix::Bt601).is_same(Pixel::new(255, 1, 0, 255))); // limited precision
+        assert!(yuv_to_rgb(63, 102, 240, QoiYuvMatrix::Bt709).is_same(Pixel::new(255, 1, 0, 255)));
+        assert!(QoiYuvLayout::I420.frame_size(3, 3) == 9 + 4 + 4);
+        assert!(QoiYuvLayout::Yuyv.frame_size(3, 3) == 8 * 3);
+    }
+    #[test]
+    const fn good_color_keys() {
+        if let Some(keys) = ColorKeys::new(&[[255, 0, 255], [0, 255, 0]], 2) {
+            assert!(keys.matches(Pixel::new(255, 0, 255, 255)));
+            assert!(keys.matches(Pixel::new(253, 2, 254, 0)));
+            assert!(!keys.matches(Pixel::new(252, 0, 255, 255))); // red outside of tolerance
+            assert!(keys.matches(Pixel::new(1, 254, 2, 255)));    // second key
+        } else {
+            unreachable!();
+        }
+        assert!(ColorKeys::new(&[], 0).is_none());
+        assert!(ColorKeys::new(&[[0; 3]; MAX_COLOR_KEYS + 1], 0).is_none());
+    }
+    #[test]
+    const fn infallible_premultiply() {
+        let pixel = premultiply(Pixel::new(255, 128, 0, 128), false);
+        assert!(pixel.is_same(Pixel::new(128, 64, 0, 128)));
+        let pixel = premultiply(Pixel::new(255, 128, 0, 128), true);
+        assert!(pixel.is_same(Pixel::new(188, 93, 0, 128)));
+        let pixel = premultiply(Pixel::new(255, 128, 0, 0), true);
+        assert!(pixel.is_same(Pixel::new(0, 0, 0, 0)));
+        let pixel = premultiply(Pixel::new(255, 128, 0, 255), true);
+        assert!(pixel.is_same(Pixel::new(255, 128, 0, 255)));
+    }
+    #[test]
+    const fn infallible_unpremultiply() {
+        let pixel = unpremultiply(Pixel::new(128, 64, 0, 128), false);
+        assert!(pixel.is_same(Pixel::new(255, 128, 0, 128)));
+        let pixel = unpremultiply(Pixel::new(200, 64, 0, 128), false);
+        assert!(pixel.is_same(Pixel::new(255, 128, 0, 128))); // clamped
+        let pixel = unpremultiply(Pixel::new(188, 93, 0, 128), true);
+        assert!(pixel.is_same(Pixel::new(255, 129, 0, 128))); // premultiplied values lose precision
+        let pixel = unpremultiply(Pixel::new(10, 20, 30, 0), true);
+        assert!(pixel.is_same(Pixel::new(0, 0, 0, 0)));
+    }
+    #[test]
+    const fn infallible_is_linear_light() {
+        assert!(QoiPremultiply::LinearLight.is_linear_light(0));
+        assert!(!QoiPremultiply::LinearLight.is_linear_light(1));
+        assert!(!QoiPremultiply::Stored.is_linear_light(0));
+    }
+}
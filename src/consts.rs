@@ -1,4 +1,7 @@
 pub const MAGIC_BYTES: [u8; 4] = [b'q', b'o', b'i', b'f'];
+/// The `8` byte end marker that follows the QOI data chunks of every QOI image.
+///
+/// It must be written after the last chunk when building an image by hand with [`QoiOp::write`](crate::QoiOp::write).
 pub const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
 pub const DEFAULT_PIXEL: crate::pixel::Pixel = crate::pixel::Pixel::new(0, 0, 0, 255);
 pub const ZERO_PIXEL: crate::pixel::Pixel = crate::pixel::Pixel::new(0, 0, 0, 0);
@@ -1,19 +1,37 @@
 use crate::{
+    color::{
+        flatten, premultiply, rgb_to_yuv, QoiBackground, QoiPremultiply, QoiYuvLayout, QoiYuvMatrix, SRGB_TO_LINEAR,
+        SRGB_TO_LINEAR_F32,
+    },
     consts::{DEFAULT_PIXEL, END_MARKER, ZERO_PIXEL},
     error::QoiError,
     header::{QoiHeader, QoiHeaderInternal},
+    op::QoiOp,
     pixel::Pixel,
+    stats::{QoiStats, DIFF, INDEX, LUMA, RGB, RGBA, RUN},
     utils::{array_from_input, is_identical}
 };
 
 /// Indicates whether the [`QoiDecoder`] is finished.
+///
+/// The output buffer holds bytes unless decoding to linear light values with
+/// [`QoiDecoder::process_chunks_linear_u16`] or [`QoiDecoder::process_chunks_linear_f32`].
 #[allow(clippy::large_enum_variant)]
-pub enum QoiDecoderProgress<const N: usize> {
+pub enum QoiDecoderProgress<const N: usize, T = u8> {
     /// Returns [`QoiDecoder`] for further processing and the filled output buffer.
-    /// The output buffer must be divisible by `4` which means it will always be full with new `4` byte RGBA pixel data.
-    Unfinished((QoiDecoder, [u8; N])),
-    /// Returns the output buffer and the amount of bytes that should be considered as free space.
-    Finished(([u8; N], usize)),
+    /// The output buffer must be divisible by `4` which means it will always be full with new `4` value RGBA pixel data.
+    Unfinished((QoiDecoder, [T; N])),
+    /// Returns the output buffer, the amount of values that should be considered as free space and the [`QoiStats`].
+    Finished(([T; N], usize, QoiStats)),
+}
+
+/// Indicates whether decoding to separate planes with [`QoiDecoder::process_chunks_planar`] is finished.
+#[allow(clippy::large_enum_variant)]
+pub enum QoiPlanarProgress<const N: usize, const P: usize> {
+    /// Returns [`QoiDecoder`] for further processing and the output planes filled with as many rows as every plane holds.
+    Unfinished((QoiDecoder, [[u8; N]; P])),
+    /// Returns the output planes, the amount of rows they hold and the [`QoiStats`].
+    Finished(([[u8; N]; P], usize, QoiStats)),
 }
 
 /// A streaming decoder for the QOI image format.
@@ -23,9 +41,11 @@ pub enum QoiDecoderProgress<const N: usize> {
 /// You can then match on [`QoiDecoderProgress`] to retrieve your buffer and either the decoder (to continue
 /// processing more chunks) or the amount of bytes that are considered free space in your buffer.
 #[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy)]
 pub struct QoiDecoder {
     state: QoiDecoderInternal,
     expected_pixels: u64, // total size of image in pixels, does not change
+    collect_stats: bool,  // whether to count the processed chunks
 }
 
 impl QoiDecoder {
@@ -49,21 +69,77 @@ impl QoiDecoder {
                     return Err(QoiError::InvalidEndMarker(end[0], end[1], end[2], end[3], end[4], end[5], end[6], end[7]));
                 }
                 let image_size = (header.width as u64) * (header.height as u64);
-                Ok((Self {state: QoiDecoderInternal::new(14, image_size), expected_pixels: image_size}, header.public()))
+                let state = QoiDecoderInternal::new(14, image_size, header.channels, header.colorspace, header.width);
+                Ok((Self {state, expected_pixels: image_size, collect_stats: false}, header.public()))
             },
             Err(e) => Err(e),
         }
     }
+    /// Enables collecting [`QoiStats`] about the processed chunks which are returned when finished.
+    ///
+    /// Collecting statistics is optional as it adds a small amount of work for every processed chunk.
+    /// Without it the returned [`QoiStats`] are empty.
+    #[must_use]
+    pub const fn with_stats(mut self) -> Self {
+        self.collect_stats = true;
+        self
+    }
+    /// Multiplies the colour values of the output pixels by their alpha value.
+    ///
+    /// QOI images store straight alpha so this is useful when the pixels are passed on to something expecting
+    /// premultiplied alpha.
+    /// The colorspace value of the header decides whether [`QoiPremultiply::LinearLight`] converts to linear light.
+    /// Only the output is affected, [`QoiStats`] still describe the stored pixels.
+    #[must_use]
+    pub const fn with_premultiplied_alpha(mut self, premultiply: QoiPremultiply) -> Self {
+        self.state.premultiply = Some(premultiply);
+        self
+    }
+    /// Composites the output pixels over a background resulting in fully opaque pixels.
+    ///
+    /// Useful when the pixels are passed on to something without alpha support such as a display or an image format
+    /// with no alpha channel.
+    /// When `linear_light` is `true` the colour values of images with a colorspace value of `0` (sRGB with linear
+    /// alpha) are mixed in linear light which avoids dark fringes around partially transparent edges.
+    /// Flattening happens before any premultiplying so every output pixel has an alpha value of `255`.
+    /// Only the output is affected, [`QoiStats`] still describe the stored pixels.
+    #[must_use]
+    pub const fn with_flattened_alpha(mut self, background: QoiBackground, linear_light: bool) -> Self {
+        self.state.flatten = Some((background, linear_light));
+        self
+    }
+    /// Replaces fully transparent output pixels (alpha `0`) with the opaque key colour.
+    ///
+    /// Useful for hardware and formats that mark transparency with a magic colour such as `255, 0, 255` (magenta).
+    /// This reverses [`QoiEncoder::new_color_keyed`](crate::QoiEncoder::new_color_keyed) and is combined with
+    /// [`QoiDecoder::with_rgb_output`] to output `3` byte pixels.
+    /// The key colour is applied before any flattening or premultiplying.
+    /// Only the output is affected, [`QoiStats`] still describe the stored pixels.
+    #[must_use]
+    pub const fn with_color_key(mut self, key: [u8; 3]) -> Self {
+        self.state.color_key = Some(key);
+        self
+    }
+    /// Outputs `3` bytes per pixel (RGB) dropping the alpha values.
+    ///
+    /// Combine with [`QoiDecoder::with_flattened_alpha`] to keep the look of transparent pixels.
+    /// Output buffers must then be divisible by `3` instead of `4`.
+    #[must_use]
+    pub const fn with_rgb_output(mut self) -> Self {
+        self.state.rgb_output = true;
+        self
+    }
     /// Processes the input bytes as QOI chunks and fills the output buffer with bytes representing RGBA pixel values.
     /// The output buffer is guaranteed to be full except on the final call.
     ///
-    /// The minimum size buffer required is `4` bytes.
+    /// The minimum size buffer required is `4` bytes (`3` bytes with [`QoiDecoder::with_rgb_output`]).
     /// This would be the least efficient buffer size as it would be the equivalent of processing `1` pixel at a time
     /// resulting in calling this method the same amount of times as there are total pixels.
     ///
     /// # Errors
     ///
-    /// Will return `Err` if output buffer is not divisible by `4` or if input data is malformed in the following ways:
+    /// Will return `Err` if output buffer is not divisible by `4` (`3` with [`QoiDecoder::with_rgb_output`]) or if input
+    /// data is malformed in the following ways:
     ///
     /// 1: The header specifies more pixels than the data contains.\
     /// 2: The header specifies less pixels than the data contains.\
@@ -72,46 +148,497 @@ impl QoiDecoder {
     pub const fn process_chunks<const N: usize>(mut self,
                                                 input: &[u8],
                                                 output: [u8; N]) -> Result<QoiDecoderProgress<N>, QoiError> {
-        if output.len() % 4 != 0 {return Err(QoiError::IncorrectBufferSize(output.len()));}
-        let (decoder, output) = self.state.process_chunks(input, output);
+        if output.len() % self.state.output_channels() != 0 {return Err(QoiError::IncorrectBufferSize(output.len()));}
+        let (decoder, output) = self.state.process(input, output, self.collect_stats);
         self.state = decoder;
-        if self.all_pixels_processed() {
-            if !self.is_byte_index_correct_for_end(input) {
-                if self.is_byte_index_too_high(input) {
-                    let last_five: [u8; 5] = array_from_input(input, input.len() - 13);
-                    let amount = 8 - (input.len() - self.state.byte_index);
-                    return Err(QoiError::EndAsChunksFinished(last_five, amount));
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space, self.state.stats))),
+            Ok(false) => Ok(QoiDecoderProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
+        }
+    }
+    /// Processes the input bytes as QOI chunks and fills the output buffer with linear light RGBA values (`0..=65535`).
+    ///
+    /// Colour values of images with a colorspace value of `0` (sRGB with linear alpha) are converted to linear light
+    /// using a lookup table built at compile time.
+    /// Alpha values and colour values of images with a colorspace value of `1` (all channels linear) are only scaled
+    /// from `0..=255` to `0..=65535`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::process_chunks`].
+    pub const fn process_chunks_linear_u16<const N: usize>(mut self,
+                                                           input: &[u8],
+                                                           mut output: [u16; N])
+                                                           -> Result<QoiDecoderProgress<N, u16>, QoiError> {
+        let channels = self.state.output_channels();
+        if output.len() % channels != 0 {return Err(QoiError::IncorrectBufferSize(output.len()));}
+        let srgb = self.state.colorspace == 0;
+        let mut output_index = 0;
+        while output_index < output.len() {
+            let (pixels, amount);
+            (self, pixels, amount) = self.process_batch(input, output.len() - output_index);
+            let mut index = 0;
+            while index < amount {
+                let value = pixels[index] as u16;
+                output[output_index] = if srgb && index % channels != 3 {SRGB_TO_LINEAR[value as usize]} else {value * 257};
+                output_index += 1;
+                index += 1;
+            }
+            if amount == 0 {break;}
+        }
+        self.state.output_buffer_space = output.len() - output_index;
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space, self.state.stats))),
+            Ok(false) => Ok(QoiDecoderProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
+        }
+    }
+    /// Processes the input bytes as QOI chunks and fills the output buffer with linear light RGBA values (`0.0..=1.0`).
+    ///
+    /// Colour values of images with a colorspace value of `0` (sRGB with linear alpha) are converted to linear light
+    /// using a lookup table built at compile time.
+    /// Alpha values and colour values of images with a colorspace value of `1` (all channels linear) are only scaled
+    /// from `0..=255` to `0.0..=1.0`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::process_chunks`].
+    pub const fn process_chunks_linear_f32<const N: usize>(mut self,
+                                                           input: &[u8],
+                                                           mut output: [f32; N])
+                                                           -> Result<QoiDecoderProgress<N, f32>, QoiError> {
+        let channels = self.state.output_channels();
+        if output.len() % channels != 0 {return Err(QoiError::IncorrectBufferSize(output.len()));}
+        let srgb = self.state.colorspace == 0;
+        let mut output_index = 0;
+        while output_index < output.len() {
+            let (pixels, amount);
+            (self, pixels, amount) = self.process_batch(input, output.len() - output_index);
+            let mut index = 0;
+            while index < amount {
+                let value = pixels[index];
+                output[output_index] = if srgb && index % channels != 3 {
+                    SRGB_TO_LINEAR_F32[value as usize]
+                } else {
+                    value as f32 / 255.0
+                };
+                output_index += 1;
+                index += 1;
+            }
+            if amount == 0 {break;}
+        }
+        self.state.output_buffer_space = output.len() - output_index;
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space, self.state.stats))),
+            Ok(false) => Ok(QoiDecoderProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
+        }
+    }
+    /// Processes the input bytes as QOI chunks and fills the output buffer with YCbCr (YUV) values.
+    ///
+    /// The output is filled with whole bands of rows sharing chroma values, `2` rows for [`QoiYuvLayout::I420`] and
+    /// [`QoiYuvLayout::Nv12`] and `1` row for [`QoiYuvLayout::Yuyv`].
+    /// A band holds the same bytes as a whole frame of the layout with the height of the band so the luma values of
+    /// both rows are followed by the chroma values of 4:2:0 bands.
+    /// The values of 4:2:0 bands must be copied to their planes while [`QoiYuvLayout::Yuyv`] bands can be used as they
+    /// are. The last band of an image with an odd height only holds `1` row.
+    ///
+    /// Chroma values are the rounded average of the pixels sharing them. Alpha values are ignored so use
+    /// [`QoiDecoder::with_flattened_alpha`] to keep the look of transparent pixels.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the output buffer is not divisible by the size of a band or under the same conditions as
+    /// [`QoiDecoder::process_chunks`].
+    #[allow(clippy::cast_possible_truncation)] // band sizes and positions within a band always fit in a usize
+    pub const fn process_chunks_yuv<const N: usize>(mut self,
+                                                    input: &[u8],
+                                                    layout: QoiYuvLayout,
+                                                    matrix: QoiYuvMatrix,
+                                                    mut output: [u8; N]) -> Result<QoiDecoderProgress<N>, QoiError> {
+        let width = self.state.width;
+        let band_size = layout.frame_size(width, layout.band_rows()) as usize;
+        if output.len() % band_size != 0 {return Err(QoiError::IncorrectBandSize(output.len(), band_size));}
+        let channels = self.state.output_channels();
+        let (row_width, chroma_width) = (width as usize, width.div_ceil(2) as usize);
+        let total_rows = self.expected_pixels / width as u64;
+        let mut position = self.expected_pixels - self.state.pixel_amount;
+        let mut output_index = 0; // start of the current band
+        let (mut x, mut row, mut rows) = (0, 0, 0);
+        let (mut sum_u, mut sum_v) = (0, 0); // chroma values of the pixels of the current pair added together
+        let mut carried = 0; // lowest bits of the chroma sums of the first row of a 4:2:0 band
+        while output_index < output.len() {
+            let bands = ((output.len() - output_index) / band_size) as u64;
+            let pixels_left = bands * layout.band_rows() as u64 * width as u64 - (row * row_width + x) as u64;
+            let space = if pixels_left > 16 {16 * channels} else {pixels_left as usize * channels};
+            let (pixels, amount);
+            (self, pixels, amount) = self.process_batch(input, space);
+            let mut index = 0;
+            while index < amount {
+                if x == 0 && row == 0 {
+                    let rows_left = total_rows - position / width as u64;
+                    rows = if rows_left < layout.band_rows() as u64 {rows_left as usize} else {layout.band_rows() as usize};
                 }
-                let difference = (input.len() - 8) - self.state.byte_index;
-                return Err(QoiError::MoreDataBeforeEnd(self.expected_pixels, difference));
+                let [y, u, v] = rgb_to_yuv(Pixel::new(pixels[index], pixels[index + 1], pixels[index + 2], 255), matrix);
+                let odd = x % 2 == 1;
+                if !odd {(sum_u, sum_v) = (0, 0);}
+                (sum_u, sum_v) = (sum_u + u as u16, sum_v + v as u16);
+                let pair_pixels = if odd {2} else {1};
+                let pair_done = odd || x + 1 == row_width;
+                match layout {
+                    QoiYuvLayout::Yuyv => {
+                        let base = output_index + x / 2 * 4;
+                        output[base + x % 2 * 2] = y;
+                        if !odd && x + 1 == row_width {output[base + 2] = y;} // single pixel repeated in the last pair
+                        if pair_done {
+                            output[base + 1] = rounded_average(sum_u, pair_pixels);
+                            output[base + 3] = rounded_average(sum_v, pair_pixels);
+                        }
+                    },
+                    QoiYuvLayout::I420 | QoiYuvLayout::Nv12 => {
+                        let luma_index = output_index + row * row_width + x;
+                        if row == 1 && !odd {carried = output[luma_index];} // read before the luma value replaces it
+                        output[luma_index] = y;
+                        let chroma = output_index + rows * row_width;
+                        let (u_index, v_index) = if matches!(layout, QoiYuvLayout::I420) {
+                            (chroma + x / 2, chroma + chroma_width + x / 2)
+                        } else {
+                            (chroma + x / 2 * 2, chroma + x / 2 * 2 + 1)
+                        };
+                        if pair_done && rows == 1 {
+                            output[u_index] = rounded_average(sum_u, pair_pixels);
+                            output[v_index] = rounded_average(sum_v, pair_pixels);
+                        } else if pair_done && row == 0 { // sums of up to 9 bits kept until the second row is reached
+                            output[u_index] = (sum_u >> 1) as u8;
+                            output[v_index] = (sum_v >> 1) as u8;
+                            output[output_index + row_width + x - x % 2] = (sum_u & 1 | (sum_v & 1) << 1) as u8;
+                        } else if pair_done {
+                            let first_u = (output[u_index] as u16) << 1 | carried as u16 & 1;
+                            let first_v = (output[v_index] as u16) << 1 | (carried as u16 >> 1) & 1;
+                            output[u_index] = rounded_average(first_u + sum_u, pair_pixels * 2);
+                            output[v_index] = rounded_average(first_v + sum_v, pair_pixels * 2);
+                        }
+                    },
+                }
+                x += 1;
+                if x == row_width {
+                    x = 0;
+                    row += 1;
+                    if row == rows {
+                        output_index += layout.frame_size(width, rows as u32) as usize;
+                        row = 0;
+                    }
+                }
+                position += 1;
+                index += channels;
             }
-            Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space)))
-        } else {
-            if self.is_byte_index_too_high(input) {
-                let last_five: [u8; 5] = array_from_input(input, input.len() - 13);
-                let amount = 8 - (input.len() - self.state.byte_index);
-                return Err(QoiError::EndAsChunksUnfinished(self.state.pixel_amount, last_five, amount));
-            } else if self.is_byte_index_correct_for_end(input) {
-                let processed_pixels = self.expected_pixels - self.state.pixel_amount;
-                return Err(QoiError::IncorrectPixelAmount(self.expected_pixels, processed_pixels));
+            if amount == 0 {break;}
+        }
+        self.state.output_buffer_space = output.len() - output_index;
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space, self.state.stats))),
+            Ok(false) => Ok(QoiDecoderProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
+        }
+    }
+    /// Processes the input bytes as QOI chunks and fills separate red, green, blue and optional alpha planes.
+    ///
+    /// Every plane holds `1` byte per pixel and every row of a plane starts at a multiple of its stride in bytes, a
+    /// stride of `0` means the rows are packed without padding. Padding bytes are left untouched.
+    /// Each call fills as many whole rows as every plane holds, the same amount for every plane.
+    /// As with [`QoiEncoder::new_planar`](crate::QoiEncoder::new_planar) the last row of a plane only needs as many
+    /// bytes as the width.
+    /// With `3` planes the alpha values are dropped and with [`QoiDecoder::with_rgb_output`] an alpha plane is filled
+    /// with `255`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the amount of planes is not `3` (RGB) or `4` (RGBA), a stride is smaller than the width, a
+    /// plane cannot hold a row or under the same conditions as [`QoiDecoder::process_chunks`] (except for the output
+    /// buffer size).
+    pub const fn process_chunks_planar<const N: usize, const P: usize>(mut self,
+                                                                       input: &[u8],
+                                                                       strides: [usize; P],
+                                                                       mut output: [[u8; N]; P])
+                                                                       -> Result<QoiPlanarProgress<N, P>, QoiError> {
+        if P != 3 && P != 4 {return Err(QoiError::InvalidPlaneAmount(P));}
+        let width = self.state.width as usize;
+        let mut strides = strides;
+        let mut rows = N;
+        let mut plane = 0;
+        while plane < P {
+            if strides[plane] == 0 {strides[plane] = width;}
+            if strides[plane] < width {return Err(QoiError::InvalidStride(strides[plane], width));}
+            if N < width {return Err(QoiError::PlaneTooSmall(plane, N, width));}
+            let fits = (N - width) / strides[plane] + 1; // the last row needs no padding
+            if fits < rows {rows = fits;}
+            plane += 1;
+        }
+        let channels = self.state.output_channels();
+        let mut written = 0;
+        while written < rows * width {
+            let pixels_left = rows * width - written;
+            let space = if pixels_left > 16 {16 * channels} else {pixels_left * channels};
+            let (pixels, amount);
+            (self, pixels, amount) = self.process_batch(input, space);
+            let mut index = 0;
+            while index < amount {
+                let (x, y) = (written % width, written / width);
+                let mut plane = 0;
+                while plane < P {
+                    output[plane][y * strides[plane] + x] = if plane < channels {pixels[index + plane]} else {255};
+                    plane += 1;
+                }
+                written += 1;
+                index += channels;
             }
-            Ok(QoiDecoderProgress::Unfinished((self, output)))
+            if amount == 0 {break;}
+        }
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiPlanarProgress::Finished((output, written.div_ceil(width), self.state.stats))),
+            Ok(false) => Ok(QoiPlanarProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
         }
     }
-    #[inline]
-    const fn all_pixels_processed(&self) -> bool {
-        self.state.pixel_amount == 0
+    /// Scans the input bytes for the distinct colours of the image and returns them as a palette.
+    ///
+    /// This is the first pass of decoding to palette indices with [`QoiDecoder::process_chunks_indexed`].
+    /// Colours are in order of first appearance so the same image always results in the same palette.
+    /// Any chosen output conversions such as [`QoiDecoder::with_flattened_alpha`] are applied to the colours first and
+    /// with [`QoiDecoder::with_rgb_output`] every colour has an alpha value of `255`.
+    /// The decoder is left untouched so it can be used for the second pass.
+    ///
+    /// Returns the palette and the amount of entries used. Limits above `256` are treated as `256`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the image has more distinct colours than the limit or under the same conditions as
+    /// [`QoiDecoder::process_chunks`] (except for the output buffer size).
+    pub const fn collect_palette(&self, input: &[u8], limit: usize) -> Result<([[u8; 4]; 256], usize), QoiError> {
+        let limit = if limit > 256 {256} else {limit};
+        let channels = self.state.output_channels();
+        let mut decoder = *self;
+        let mut palette = [[0; 4]; 256];
+        let mut colors = 0;
+        let mut cache = [0; 64];
+        loop {
+            let (pixels, amount);
+            (decoder, pixels, amount) = decoder.process_batch(input, 64 - 64 % channels);
+            let mut index = 0;
+            while index < amount {
+                let color = palette_color(&pixels, index, channels);
+                let found;
+                (cache, found) = find_color(&palette, colors, cache, color);
+                if found.is_none() {
+                    if colors == limit {return Err(QoiError::TooManyColors(limit));}
+                    palette[colors] = color;
+                    colors += 1;
+                }
+                index += channels;
+            }
+            if amount == 0 {break;}
+        }
+        match check_progress(input, decoder.state.byte_index, decoder.state.pixel_amount, decoder.expected_pixels) {
+            Ok(_) => Ok((palette, colors)),
+            Err(e) => Err(e),
+        }
     }
-    #[inline]
-    const fn is_byte_index_correct_for_end(&self, input: &[u8]) -> bool {
-        self.state.byte_index == input.len() - 8
+    /// Processes the input bytes as QOI chunks and fills the output buffer with the palette index of every pixel.
+    ///
+    /// This is the second pass of decoding to palette indices after [`QoiDecoder::collect_palette`].
+    /// Every byte of output is the index of `1` pixel so any buffer size can be used.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a colour is not in the palette or under the same conditions as
+    /// [`QoiDecoder::process_chunks`] (except for the output buffer size).
+    pub const fn process_chunks_indexed<const N: usize>(mut self,
+                                                        input: &[u8],
+                                                        palette: &[[u8; 4]],
+                                                        mut output: [u8; N]) -> Result<QoiDecoderProgress<N>, QoiError> {
+        let channels = self.state.output_channels();
+        let mut cache = [0; 64];
+        let mut output_index = 0;
+        while output_index < output.len() {
+            let (pixels, amount);
+            (self, pixels, amount) = self.process_batch(input, (output.len() - output_index) * channels);
+            let first_position = self.expected_pixels - self.state.pixel_amount - (amount / channels) as u64;
+            let mut index = 0;
+            while index < amount {
+                let found;
+                (cache, found) = find_color(palette, palette.len(), cache, palette_color(&pixels, index, channels));
+                match found {
+                    Some(found) => output[output_index] = found,
+                    None => return Err(QoiError::ColorNotInPalette(first_position + (index / channels) as u64)),
+                }
+                output_index += 1;
+                index += channels;
+            }
+            if amount == 0 {break;}
+        }
+        self.state.output_buffer_space = output.len() - output_index;
+        match check_progress(input, self.state.byte_index, self.state.pixel_amount, self.expected_pixels) {
+            Ok(true) => Ok(QoiDecoderProgress::Finished((output, self.state.output_buffer_space, self.state.stats))),
+            Ok(false) => Ok(QoiDecoderProgress::Unfinished((self, output))),
+            Err(e) => Err(e),
+        }
     }
+    // decodes up to 64 bytes of pixels, limited by the space left in the output, returning them and the amount used
     #[inline]
-    const fn is_byte_index_too_high(&self, input: &[u8]) -> bool {
-        self.state.byte_index > input.len() - 8
+    const fn process_batch(mut self, input: &[u8], space: usize) -> (Self, [u8; 64], usize) {
+        let mut pixels = [0; 64];
+        let mut amount = 0;
+        let rgb_output = self.state.rgb_output;
+        self.state.rgb_output = false; // single pixels are decoded as RGBA and the alpha value dropped below
+        while amount < space && amount < 61 && self.state.pixel_amount != 0 && self.state.is_byte_index_safe(input) {
+            let pixel;
+            (self.state, pixel) = self.state.process(input, [0; 4], self.collect_stats);
+            if self.state.output_buffer_space != 0 {break;}
+            pixels[amount] = pixel[0];
+            pixels[amount + 1] = pixel[1];
+            pixels[amount + 2] = pixel[2];
+            if rgb_output {
+                amount += 3;
+            } else {
+                pixels[amount + 3] = pixel[3];
+                amount += 4;
+            }
+        }
+        self.state.rgb_output = rgb_output;
+        (self, pixels, amount)
+    }
+    /// Checks that the input bytes are a valid QOI image without producing any pixel data.
+    ///
+    /// All chunks are walked to confirm the amount of pixels matches the header, that the end marker is correct and
+    /// that no chunk reads into the end marker.
+    /// This is faster than decoding as no pixel data is written and the pixel values are only calculated until one with
+    /// an alpha value other than `255` is found, which is needed for [`QoiStats::alpha_used`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::new`] and [`QoiDecoder::process_chunks`] (except for
+    /// the output buffer size).
+    pub const fn validate(input: &[u8]) -> Result<QoiValidationReport, QoiError> {
+        let header = match Self::new(input) {
+            Ok((_, header)) => header,
+            Err(e) => return Err(e),
+        };
+        let expected_pixels = header.pixel_amount();
+        let mut stats = QoiStats::new(header.channels());
+        let mut pixel_amount = expected_pixels;
+        let mut byte_index = 14;
+        let mut seen_pixels = [ZERO_PIXEL; 64];
+        let mut previous_pixel = DEFAULT_PIXEL;
+        while pixel_amount != 0 && byte_index < input.len() - 8 {
+            let tag = input[byte_index];
+            let (chunk, size, pixels) = match tag {
+                254 => (RGB, 4, 1),                             // QOI_OP_RGB:   8bit tag, 3x8bit data
+                255 => (RGBA, 5, 1),                            // QOI_OP_RGBA:  8bit tag, 4x8bit data
+                0..=63 => (INDEX, 1, 1),                        // QOI_OP_INDEX: 2bit tag, 6bit val
+                64..=127 => (DIFF, 1, 1),                       // QOI_OP_DIFF:  2bit tag, 3x2bit vals
+                128..=191 => (LUMA, 2, 1),                      // QOI_OP_LUMA:  2bit tag, 6bit val, 2x4bit vals
+                192..=253 => (RUN, 1, (tag & 0x3f) as u64 + 1), // QOI_OP_RUN:   2bit tag, 6bit val, bias -1
+            };
+            if pixels > pixel_amount { // run chunk continues past the amount of pixels in the header
+                return Err(QoiError::MoreDataBeforeEnd(expected_pixels, (input.len() - 8) - byte_index));
+            }
+            stats = stats.record(chunk, pixels);
+            if !stats.alpha_used() { // an index chunk can refer to an unset 0, 0, 0, 0 pixel so alpha needs the pixels
+                previous_pixel = QoiOp::from_input(input, byte_index).apply(previous_pixel, &seen_pixels);
+                seen_pixels[previous_pixel.calculate_hash_index()] = previous_pixel;
+                stats = stats.record_alpha(previous_pixel.alpha);
+            }
+            pixel_amount -= pixels;
+            byte_index += size;
+        }
+        match check_progress(input, byte_index, pixel_amount, expected_pixels) {
+            Ok(_) => Ok(QoiValidationReport {header, stats}),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The result of successfully validating a QOI image with [`QoiDecoder::validate`].
+#[allow(clippy::module_name_repetitions)]
+pub struct QoiValidationReport {
+    header: QoiHeader,
+    stats: QoiStats,
+}
+
+impl QoiValidationReport {
+    /// The header of the validated QOI image.
+    #[must_use]
+    pub const fn header(&self) -> &QoiHeader {
+        &self.header
+    }
+    /// The amount of pixels found in the QOI data chunks which always matches the header.
+    #[must_use]
+    pub const fn pixel_amount(&self) -> u64 {
+        self.stats.pixel_amount()
+    }
+    /// Statistics about the QOI data chunks found while validating.
+    #[must_use]
+    pub const fn stats(&self) -> QoiStats {
+        self.stats
     }
 }
 
+// determines whether processing is finished by comparing the input index with the end marker. returns true when finished
+pub const fn check_progress(input: &[u8], byte_index: usize, pixel_amount: u64, expected_pixels: u64) -> Result<bool, QoiError> {
+    let end = input.len() - 8;
+    if pixel_amount == 0 {
+        if byte_index > end {
+            let last_five: [u8; 5] = array_from_input(input, input.len() - 13);
+            return Err(QoiError::EndAsChunksFinished(last_five, byte_index - end));
+        } else if byte_index < end {
+            return Err(QoiError::MoreDataBeforeEnd(expected_pixels, end - byte_index));
+        }
+        Ok(true)
+    } else {
+        if byte_index > end {
+            let last_five: [u8; 5] = array_from_input(input, input.len() - 13);
+            return Err(QoiError::EndAsChunksUnfinished(pixel_amount, last_five, byte_index - end));
+        } else if byte_index == end {
+            return Err(QoiError::IncorrectPixelAmount(expected_pixels, expected_pixels - pixel_amount));
+        }
+        Ok(false)
+    }
+}
+
+// the average of an amount of u8 values added together, rounded once with halves rounded up
+#[allow(clippy::cast_possible_truncation)] // average of u8 values always fits in a u8
+#[inline]
+const fn rounded_average(sum: u16, amount: u16) -> u8 {
+    ((sum + amount / 2) / amount) as u8
+}
+
+// the colour of the pixel at the index of decoded values, opaque when the values are RGB
+#[inline]
+const fn palette_color(pixels: &[u8], index: usize, channels: usize) -> [u8; 4] {
+    let alpha = if channels == 4 {pixels[index + 3]} else {255};
+    [pixels[index], pixels[index + 1], pixels[index + 2], alpha]
+}
+
+// finds the colour in the used entries of the palette. found entries are remembered by hash to avoid most searches
+#[allow(clippy::cast_possible_truncation)] // palettes have at most 256 entries so cannot truncate when casting to u8
+#[inline]
+const fn find_color(palette: &[[u8; 4]], colors: usize, mut cache: [u8; 64], color: [u8; 4]) -> ([u8; 64], Option<u8>) {
+    let hash = Pixel::new(color[0], color[1], color[2], color[3]).calculate_hash_index();
+    let cached = cache[hash] as usize;
+    if cached < colors && is_identical(&palette[cached], &color) {return (cache, Some(cached as u8));}
+    let mut entry = 0;
+    while entry < colors && entry < 256 {
+        if is_identical(&palette[entry], &color) {
+            cache[hash] = entry as u8;
+            return (cache, Some(entry as u8));
+        }
+        entry += 1;
+    }
+    (cache, None)
+}
+
+#[derive(Clone, Copy)]
 struct QoiDecoderInternal {
     byte_index: usize,          // keeps track of input index, always increments
     seen_pixels: [Pixel; 64],
@@ -119,10 +646,18 @@ struct QoiDecoderInternal {
     pixel_amount: u64,          // keeps track of pixels to process, always decrements
     output_buffer_space: usize, // last process_chunks may end in space in the output
     run_amount: u8,             // keeps track of processing run chunk when output buffer full
+    stats: QoiStats,            // only updated when collecting statistics
+    premultiply: Option<QoiPremultiply>,
+    colorspace: u8,             // decides whether premultiplying in linear light converts the colour values
+    flatten: Option<(QoiBackground, bool)>, // background and whether to mix in linear light
+    rgb_output: bool,           // writes 3 bytes per pixel instead of 4
+    color_key: Option<[u8; 3]>, // colour of fully transparent pixels
+    width: u32,                 // used with the total pixels to find the position of a pixel for checkerboards
+    total_pixels: u64,
 }
 
 impl QoiDecoderInternal {
-    const fn new(byte_index: usize, pixel_amount: u64) -> Self {
+    const fn new(byte_index: usize, pixel_amount: u64, channels: u8, colorspace: u8, width: u32) -> Self {
         Self {
             byte_index,
             seen_pixels: [ZERO_PIXEL; 64],
@@ -130,10 +665,31 @@ impl QoiDecoderInternal {
             pixel_amount,
             output_buffer_space: 0,
             run_amount: 0,
+            stats: QoiStats::new(channels),
+            premultiply: None,
+            colorspace,
+            flatten: None,
+            rgb_output: false,
+            color_key: None,
+            width,
+            total_pixels: pixel_amount,
         }
     }
+    // picks the chunk loop for the options so the default RGBA output skips every per pixel conversion
     #[inline]
-    const fn process_chunks<const N: usize>(mut self, input: &[u8], mut output: [u8; N]) -> (Self, [u8; N]) {
+    const fn process<const N: usize>(self, input: &[u8], output: [u8; N], stats: bool) -> (Self, [u8; N]) {
+        match (stats, self.converts_output()) {
+            (false, false) => self.process_chunks::<N, false, false>(input, output),
+            (false, true) => self.process_chunks::<N, false, true>(input, output),
+            (true, false) => self.process_chunks::<N, true, false>(input, output),
+            (true, true) => self.process_chunks::<N, true, true>(input, output),
+        }
+    }
+    #[inline]
+    const fn process_chunks<const N: usize, const STATS: bool, const CONVERT: bool>(mut self,
+                                                                                    input: &[u8],
+                                                                                    mut output: [u8; N])
+                                                                                    -> (Self, [u8; N]) {
         let mut output_index = 0;
         while self.pixel_amount != 0 && self.is_byte_index_safe(input) {
             let tag = input[self.byte_index];
@@ -141,12 +697,14 @@ impl QoiDecoderInternal {
             let mut run = false;
             match tag {
                 254 => { // QOI_OP_RGB: 8bit tag (11111110)
+                    if STATS {self.stats = self.stats.record(RGB, 1);}
                     self.byte_index += 1;
                     current_pixel.red = input[self.byte_index]; self.byte_index += 1;
                     current_pixel.green = input[self.byte_index]; self.byte_index += 1;
                     current_pixel.blue = input[self.byte_index]; self.byte_index += 1;
                 },
                 255 => { // QOI_OP_RGBA: 8bit tag (11111111)
+                    if STATS {self.stats = self.stats.record(RGBA, 1);}
                     self.byte_index += 1;
                     current_pixel.red = input[self.byte_index]; self.byte_index += 1;
                     current_pixel.green = input[self.byte_index]; self.byte_index += 1;
@@ -154,16 +712,19 @@ impl QoiDecoderInternal {
                     current_pixel.alpha = input[self.byte_index]; self.byte_index += 1;
                 },
                 0..=63 => { // QOI_OP_INDEX:  2bit tag (00), 6bit val (000000)
+                    if STATS {self.stats = self.stats.record(INDEX, 1);}
                     self.byte_index += 1;
                     current_pixel = self.seen_pixels[tag as usize];
                 },
                 64..=127 => { // QOI_OP_DIFF: 2bit tag (01), 3x2bit vals (00) rgb diffs, bias 2 (0 means -2)
+                    if STATS {self.stats = self.stats.record(DIFF, 1);}
                     self.byte_index += 1;
                     current_pixel.red = current_pixel.red.wrapping_add((tag >> 4) & 0x03).wrapping_sub(2);
                     current_pixel.green = current_pixel.green.wrapping_add((tag >> 2) & 0x03).wrapping_sub(2);
                     current_pixel.blue = current_pixel.blue.wrapping_add(tag & 0x03).wrapping_sub(2);
                 },
                 128..=191 => { // QOI_OP_LUMA: 2bit tag (10), 6bit val (000000) green diff, bias 32 (0 means -32)
+                    if STATS {self.stats = self.stats.record(LUMA, 1);}
                     self.byte_index += 1;
                     let green_diff = (tag & 0x3f).wrapping_sub(32); // clear tag with bitwise AND, include bias
                     let from_green = green_diff.wrapping_sub(8); // include bias, used for red and blue diff calcs
@@ -173,24 +734,44 @@ impl QoiDecoderInternal {
                     current_pixel.blue = current_pixel.blue.wrapping_add(from_green.wrapping_add(red_and_blue & 0x0f));
                 },
                 192..=253 => { // QOI_OP_RUN: 2bit tag (11), 6bit val (000000), bias -1 (0 means 1)
-                    if self.run_amount == 0 {self.run_amount = (tag & 0x3f) + 1;} // clear tag with bitwise AND, include bias
-                    while self.run_amount != 0 {
-                        if output_index == output.len() {break;}
-                        (output, output_index) = current_pixel.to_output(output, output_index);
-                        self.pixel_amount -= 1;
-                        self.run_amount -= 1;
+                    if self.run_amount == 0 {
+                        self.run_amount = (tag & 0x3f) + 1; // clear tag with bitwise AND, include bias
+                        if STATS {self.stats = self.stats.record(RUN, self.run_amount as u64);}
+                    }
+                    if CONVERT {
+                        let mut output_pixel = self.output_pixel(current_pixel);
+                        let varies = self.varies_by_position();
+                        while self.run_amount != 0 && self.pixel_amount != 0 { // header may understate pixels
+                            if output_index == output.len() {break;}
+                            if varies {output_pixel = self.output_pixel(current_pixel);}
+                            (output, output_index) = self.write_pixel(output_pixel, output, output_index);
+                            self.pixel_amount -= 1;
+                            self.run_amount -= 1;
+                        }
+                    } else {
+                        while self.run_amount != 0 && self.pixel_amount != 0 { // header may understate pixels
+                            if output_index == output.len() {break;}
+                            (output, output_index) = current_pixel.to_output(output, output_index);
+                            self.pixel_amount -= 1;
+                            self.run_amount -= 1;
+                        }
                     }
                     if self.run_amount == 0 {self.byte_index += 1;}
                     run = true;
                 },
             }
             if !run {
-                (output, output_index) = current_pixel.to_output(output, output_index);
+                (output, output_index) = if CONVERT {
+                    self.write_pixel(self.output_pixel(current_pixel), output, output_index)
+                } else {
+                    current_pixel.to_output(output, output_index)
+                };
                 self.pixel_amount -= 1;
             }
             let index = current_pixel.calculate_hash_index();
             self.seen_pixels[index] = current_pixel;
             self.previous_pixel = current_pixel;
+            if STATS {self.stats = self.stats.record_alpha(current_pixel.alpha);}
             if output_index == output.len() {break;}
         }
         self.output_buffer_space = output.len() - output_index;
@@ -200,12 +781,57 @@ impl QoiDecoderInternal {
     const fn is_byte_index_safe(&self, input: &[u8]) -> bool {
         self.byte_index < (input.len() - 8)
     }
+    // applies any requested changes to a pixel before it is written to the output
+    #[inline]
+    const fn output_pixel(&self, mut pixel: Pixel) -> Pixel {
+        if let Some(key) = self.color_key {
+            if pixel.alpha == 0 {pixel = Pixel::new(key[0], key[1], key[2], 255);}
+        }
+        let pixel = match self.flatten {
+            Some((background, linear_light)) => {
+                let position = self.total_pixels - self.pixel_amount;
+                #[allow(clippy::cast_possible_truncation)] // position within a row or column always fits in a u32
+                let (x, y) = ((position % self.width as u64) as u32, (position / self.width as u64) as u32);
+                flatten(pixel, background.color_at(x, y), linear_light && self.colorspace == 0)
+            },
+            None => pixel,
+        };
+        match self.premultiply {
+            Some(mode) => premultiply(pixel, mode.is_linear_light(self.colorspace)),
+            None => pixel,
+        }
+    }
+    // whether any output option changes the decoded pixels or drops their alpha value
+    #[inline]
+    const fn converts_output(&self) -> bool {
+        self.premultiply.is_some() || self.flatten.is_some() || self.color_key.is_some() || self.rgb_output
+    }
+    // whether the output of identical pixels depends on their position in the image
+    #[inline]
+    const fn varies_by_position(&self) -> bool {
+        match self.flatten {
+            Some((background, _)) => background.is_checkerboard(),
+            None => false,
+        }
+    }
+    #[inline]
+    const fn write_pixel<const N: usize>(&self, pixel: Pixel, output: [u8; N], index: usize) -> ([u8; N], usize) {
+        if self.rgb_output {pixel.to_rgb_output(output, index)} else {pixel.to_output(output, index)}
+    }
+    #[inline]
+    const fn output_channels(&self) -> usize {
+        if self.rgb_output {3} else {4}
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{error::QoiError, utils::is_identical};
-    use super::{QoiDecoder, QoiDecoderProgress};
+    use crate::{
+        color::{QoiBackground, QoiPremultiply, QoiYuvLayout, QoiYuvMatrix, SRGB_TO_LINEAR, SRGB_TO_LINEAR_F32},
+        error::QoiError,
+        utils::is_identical,
+    };
+    use super::{QoiDecoder, QoiDecoderProgress, QoiPlanarProgress};
     #[test]
     const fn good_new() {
         let input = [113, 111, 105, 102,      // magic bytes (qoif)
@@ -380,7 +1006,7 @@ mod tests {
             assert!(progress.is_ok());
             if let Ok(progress) = progress {
                 match progress {
-                    QoiDecoderProgress::Finished((buffer, empty_space)) => {
+                    QoiDecoderProgress::Finished((buffer, empty_space, _)) => {
                         assert!(is_identical(&buffer, &[255; 32]));
                         assert!(empty_space == 0);
                     },
@@ -390,6 +1016,51 @@ mod tests {
         }
     }
     #[test]
+    const fn good_process_chunks_stats() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                     3,                       // channels (3 = RGB)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 255, 255, 255,      // RGB chunk
+                     127,                     // Diff chunk (r+1, g+1, b+1)
+                     128, 55,                 // Luma chunk (r-37, g-32, b-33)
+                     38,                      // Index chunk
+                     195,                     // Run chunk (amount 4)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let both = QoiDecoder::new(&input);
+        assert!(both.is_ok());
+        if let Ok((decoder, _)) = both {
+            let progress = decoder.with_stats().process_chunks(&input, [0; 32]);
+            assert!(progress.is_ok());
+            if let Ok(QoiDecoderProgress::Finished((_, empty_space, stats))) = progress {
+                assert!(empty_space == 0);
+                assert!(stats.rgb_chunks() == 1);
+                assert!(stats.rgba_chunks() == 0);
+                assert!(stats.index_chunks() == 1);
+                assert!(stats.diff_chunks() == 1);
+                assert!(stats.luma_chunks() == 1);
+                assert!(stats.run_chunks() == 1);
+                assert!(!stats.alpha_used());
+                assert!(stats.total_chunk_bytes() == 9);
+                assert!(stats.pixel_amount() == 8);
+                assert!(stats.longest_run() == 4);
+                assert!(matches!(stats.index_hit_rate(), (1, 4)));
+                assert!(matches!(stats.compression_ratio(), (31, 24))); // 9 chunk bytes + 22, 8 pixels of 3 bytes each
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) { // not collecting statistics
+            if let Ok(QoiDecoderProgress::Finished((_, _, stats))) = decoder.process_chunks(&input, [0; 32]) {
+                assert!(stats.total_chunks() == 0);
+                assert!(stats.pixel_amount() == 0);
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
     const fn bad_buffer_size() {
         let input = [113, 111, 105, 102,      // magic bytes (qoif)
                      0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
@@ -503,6 +1174,34 @@ mod tests {
         }
     }
     #[test]
+    const fn bad_process_chunks_header_understates_pixels_run_past_end() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     198,                     // Run chunk (amount 7, header only specifies 2 pixels)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let both = QoiDecoder::new(&input);
+        assert!(both.is_ok());
+        if let Ok((decoder, _)) = both {
+            let output = [0; 32];
+            let progress = decoder.process_chunks(&input, output);
+            assert!(progress.is_err());
+            if let Err(e) = progress {
+                match e {
+                    QoiError::MoreDataBeforeEnd(header, chunk_bytes) => {
+                        assert!(header == 2);
+                        assert!(chunk_bytes == 1);
+                    },
+                    _ => unreachable!(),
+                }
+            }
+        }
+        let report = QoiDecoder::validate(&input);
+        assert!(matches!(report, Err(QoiError::MoreDataBeforeEnd(2, 1))));
+    }
+    #[test]
     const fn bad_process_chunks_header_states_correct_pixels_index_into_end() {
         let input = [113, 111, 105, 102,      // magic bytes (qoif)
                      0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
@@ -530,5 +1229,417 @@ mod tests {
                 }
             }
         }
+        let report = QoiDecoder::validate(&input);
+        assert!(matches!(report, Err(QoiError::EndAsChunksFinished([255, 253, 255, 252, 255], 4))));
+    }
+    #[test]
+    const fn good_process_chunks_premultiplied() {
+        let mut input = [113, 111, 105, 102,      // magic bytes (qoif)
+                         0, 0, 0, 3,              // width (4xu8 into 1xu32 big endian: 3)
+                         0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                         4,                       // channels (4 = RGBA)
+                         0,                       // colorspace (0 = sRGB with linear alpha)
+                         255, 255, 128, 0, 128,   // RGBA chunk
+                         192,                     // Run chunk (amount 1)
+                         254, 10, 20, 30,         // RGB chunk
+                         0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let mut colorspace = 0;
+        while colorspace < 2 {
+            input[13] = colorspace;
+            let expected = if colorspace == 0 {[188, 93, 0, 128]} else {[128, 64, 0, 128]}; // linear light if sRGB
+            let third = if colorspace == 0 {[5, 12, 19, 128]} else {[5, 10, 15, 128]}; // stored pixel keeps previous alpha
+            if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+                let progress = decoder.with_premultiplied_alpha(QoiPremultiply::LinearLight).process_chunks(&input, [0; 12]);
+                if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = progress {
+                    assert!(empty == 0);
+                    assert!(is_identical(buffer.split_at(8).0, &[expected[0], expected[1], expected[2], expected[3],
+                                                                 expected[0], expected[1], expected[2], expected[3]]));
+                    assert!(is_identical(buffer.split_at(8).1, &third));
+                } else {
+                    unreachable!();
+                }
+            }
+            colorspace += 1;
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.with_premultiplied_alpha(QoiPremultiply::Stored).process_chunks(&input, [0; 4]);
+            if let Ok(QoiDecoderProgress::Unfinished((_, buffer))) = progress {
+                assert!(is_identical(&buffer, &[128, 64, 0, 128]));
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_chunks_flattened() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 2,              // height (4xu8 into 1xu32 big endian: 2)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 255, 0, 0, 0,       // RGBA chunk
+                     192,                     // Run chunk (amount 1)
+                     255, 255, 0, 0, 128,     // RGBA chunk
+                     192,                     // Run chunk (amount 1)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let background = QoiBackground::Checkerboard {size: 1, light: [255, 255, 255], dark: [0, 0, 0]};
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let decoder = decoder.with_flattened_alpha(background, false).with_rgb_output();
+            assert!(matches!(decoder.process_chunks(&input, [0; 4]), Err(QoiError::IncorrectBufferSize(4))));
+            if let Ok(QoiDecoderProgress::Unfinished((decoder, buffer))) = decoder.process_chunks(&input, [0; 6]) {
+                assert!(is_identical(&buffer, &[255, 255, 255, 0, 0, 0])); // run pixel changes square
+                if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks(&input, [0; 9]) {
+                    assert!(empty == 3);
+                    assert!(is_identical(buffer.split_at(6).0, &[128, 0, 0, 255, 127, 127]));
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let decoder = decoder.with_flattened_alpha(QoiBackground::Solid([0, 0, 255]), true);
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks(&input, [0; 16]) {
+                assert!(empty == 0);
+                assert!(is_identical(buffer.split_at(8).0, &[0, 0, 255, 255, 0, 0, 255, 255]));
+                assert!(is_identical(buffer.split_at(8).1, &[188, 0, 187, 255, 188, 0, 187, 255])); // mixed in linear light
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let decoder = decoder.with_flattened_alpha(background, false).with_rgb_output();
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks_linear_u16(&input, [0; 12]) {
+                assert!(empty == 0);
+                assert!(buffer[0] == 65535 && buffer[3] == 0 && buffer[6] == SRGB_TO_LINEAR[128] && buffer[11] == SRGB_TO_LINEAR[127]);
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_chunks_color_key() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 3,              // width (4xu8 into 1xu32 big endian: 3)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 0, 0, 0, 0,         // RGBA chunk
+                     255, 10, 20, 30, 255,    // RGBA chunk
+                     255, 10, 20, 30, 1,      // RGBA chunk
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let decoder = decoder.with_color_key([255, 0, 255]).with_rgb_output().with_stats();
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, stats))) = decoder.process_chunks(&input, [0; 9]) {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, &[255, 0, 255, 10, 20, 30, 10, 20, 30])); // only alpha 0 is keyed
+                assert!(stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_chunks_indexed() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 3,              // width (4xu8 into 1xu32 big endian: 3)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 10, 20, 30,         // RGB chunk
+                     192,                     // Run chunk (amount 1)
+                     255, 1, 2, 3, 4,         // RGBA chunk
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            assert!(matches!(decoder.collect_palette(&input, 1), Err(QoiError::TooManyColors(1))));
+            if let Ok((palette, colors)) = decoder.collect_palette(&input, 1000) {
+                assert!(colors == 2);
+                assert!(is_identical(&palette[0], &[10, 20, 30, 255]) && is_identical(&palette[1], &[1, 2, 3, 4]));
+                let palette = palette.split_at(colors).0;
+                if let Ok(QoiDecoderProgress::Unfinished((decoder, buffer))) = decoder.process_chunks_indexed(&input, palette, [9; 2]) {
+                    assert!(is_identical(&buffer, &[0, 0]));
+                    if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks_indexed(&input, palette, [9; 2]) {
+                        assert!(empty == 1 && buffer[0] == 1);
+                    } else {
+                        unreachable!();
+                    }
+                } else {
+                    unreachable!();
+                }
+                assert!(matches!(decoder.process_chunks_indexed(&input, palette.split_at(1).0, [0; 3]),
+                                 Err(QoiError::ColorNotInPalette(2))));
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            if let Ok((palette, colors)) = decoder.with_rgb_output().collect_palette(&input, 256) {
+                assert!(colors == 2 && is_identical(&palette[1], &[1, 2, 3, 255])); // alpha dropped
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_chunks_yuv() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 3,              // width (4xu8 into 1xu32 big endian: 3)
+                     0, 0, 0, 3,              // height (4xu8 into 1xu32 big endian: 3)
+                     3,                       // channels (3 = RGB)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 255, 0, 0,          // RGB chunk
+                     254, 0, 255, 0,          // RGB chunk
+                     254, 0, 0, 255,          // RGB chunk
+                     254, 255, 255, 255,      // RGB chunk
+                     254, 0, 0, 0,            // RGB chunk
+                     254, 10, 20, 30,         // RGB chunk
+                     254, 200, 100, 50,       // RGB chunk
+                     254, 200, 100, 50,       // RGB chunk
+                     254, 255, 255, 0,        // RGB chunk
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            assert!(matches!(decoder.process_chunks_yuv(&input, QoiYuvLayout::I420, QoiYuvMatrix::Bt601, [0; 12]),
+                             Err(QoiError::IncorrectBandSize(12, 10))));
+            let progress = decoder.process_chunks_yuv(&input, QoiYuvLayout::I420, QoiYuvMatrix::Bt601, [0; 10]);
+            if let Ok(QoiDecoderProgress::Unfinished((decoder, buffer))) = progress {
+                assert!(is_identical(&buffer, &[82, 144, 41, 235, 16, 32, // luma of 2 rows
+                                                100, 187,                 // Cb (U)
+                                                133, 117]));              // Cr (V)
+                let progress = decoder.process_chunks_yuv(&input, QoiYuvLayout::I420, QoiYuvMatrix::Bt601, [0; 10]);
+                if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = progress {
+                    assert!(empty == 3);
+                    assert!(is_identical(buffer.split_at(7).0, &[123, 123, 210, 91, 16, 175, 146])); // last row alone
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.process_chunks_yuv(&input, QoiYuvLayout::Nv12, QoiYuvMatrix::Bt601, [0; 20]);
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = progress {
+                assert!(empty == 3);
+                assert!(is_identical(buffer.split_at(17).0, &[82, 144, 41, 235, 16, 32, 100, 133, 187, 117,
+                                                              123, 123, 210, 91, 175, 16, 146])); // Cb and Cr interleaved
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.process_chunks_yuv(&input, QoiYuvLayout::Yuyv, QoiYuvMatrix::Bt601, [0; 24]);
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = progress {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, &[82, 72, 144, 137, 41, 240, 41, 110,  // last luma value repeated
+                                                235, 128, 16, 128, 32, 134, 32, 123,
+                                                123, 91, 123, 175, 210, 16, 210, 146]));
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_chunks_yuv_rounding() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 2,              // height (4xu8 into 1xu32 big endian: 2)
+                     3,                       // channels (3 = RGB)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     194,                     // Run chunk (amount 3), Cb (U) 128 each
+                     160, 138,                // Luma chunk (r+0, g+0, b+2), Cb (U) 129
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.process_chunks_yuv(&input, QoiYuvLayout::I420, QoiYuvMatrix::Bt601, [0; 6]);
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = progress {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, &[16, 16, 16, 16, // luma of 2 rows
+                                                128,            // Cb (U) average of 128, 128, 128, 129 rounded once
+                                                128]));         // Cr (V)
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn good_process_chunks_planar() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 3,              // height (4xu8 into 1xu32 big endian: 3)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 1, 2, 3, 4,         // RGBA chunk
+                     255, 5, 6, 7, 8,         // RGBA chunk
+                     255, 9, 10, 11, 12,      // RGBA chunk
+                     255, 13, 14, 15, 16,     // RGBA chunk
+                     255, 17, 18, 19, 20,     // RGBA chunk
+                     255, 21, 22, 23, 24,     // RGBA chunk
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.process_chunks_planar(&input, [2, 3, 0, 2], [[0; 6]; 4]);
+            if let Ok(QoiPlanarProgress::Unfinished((decoder, planes))) = progress {
+                assert!(is_identical(&planes[0], &[1, 5, 9, 13, 0, 0]));
+                assert!(is_identical(&planes[1], &[2, 6, 0, 10, 14, 0])); // padding of 1 byte after every row
+                assert!(is_identical(&planes[2], &[3, 7, 11, 15, 0, 0]));
+                assert!(is_identical(&planes[3], &[4, 8, 12, 16, 0, 0]));
+                let progress = decoder.process_chunks_planar(&input, [2, 3, 0, 2], [[0; 6]; 4]);
+                if let Ok(QoiPlanarProgress::Finished((planes, rows, _))) = progress {
+                    assert!(rows == 1);
+                    assert!(is_identical(planes[1].split_at(2).0, &[18, 22]));
+                    assert!(is_identical(planes[3].split_at(2).0, &[20, 24]));
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            let progress = decoder.process_chunks_planar(&input, [0; 3], [[0; 6]; 3]);
+            if let Ok(QoiPlanarProgress::Finished((planes, rows, _))) = progress {
+                assert!(rows == 3);
+                assert!(is_identical(&planes[2], &[3, 7, 11, 15, 19, 23])); // alpha dropped
+            } else {
+                unreachable!();
+            }
+            let progress = decoder.process_chunks_planar(&input, [0, 3, 0], [[0; 5]; 3]);
+            if let Ok(QoiPlanarProgress::Unfinished((_, planes))) = progress {
+                assert!(is_identical(&planes[1], &[2, 6, 0, 10, 14])); // 2 rows without padding after the last one
+            } else {
+                unreachable!();
+            }
+            assert!(matches!(decoder.process_chunks_planar(&input, [0; 2], [[0; 6]; 2]),
+                             Err(QoiError::InvalidPlaneAmount(2))));
+            assert!(matches!(decoder.process_chunks_planar(&input, [0, 1, 0], [[0; 6]; 3]),
+                             Err(QoiError::InvalidStride(1, 2))));
+            assert!(matches!(decoder.process_chunks_planar(&input, [0, 3, 0], [[0; 1]; 3]),
+                             Err(QoiError::PlaneTooSmall(0, 1, 2))));
+        }
+    }
+    #[test]
+    const fn good_process_chunks_linear() {
+        let mut input = [113, 111, 105, 102,      // magic bytes (qoif)
+                         0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                         0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                         4,                       // channels (4 = RGBA)
+                         0,                       // colorspace (0 = sRGB with linear alpha)
+                         255, 128, 0, 255, 128,   // RGBA chunk
+                         254, 1, 2, 3,            // RGB chunk
+                         0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks_linear_u16(&input, [0; 12]) {
+                assert!(empty == 4);
+                assert!(buffer[0] == 14146 && buffer[1] == 0 && buffer[2] == 65535 && buffer[3] == 32896); // alpha scaled
+                assert!(buffer[4] == 20 && buffer[5] == 40 && buffer[6] == 60 && buffer[7] == 32896);
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            if let Ok(QoiDecoderProgress::Unfinished((decoder, buffer))) = decoder.process_chunks_linear_f32(&input, [0.0; 4]) {
+                assert!(buffer[0] == SRGB_TO_LINEAR_F32[128] && buffer[1] == 0.0 && buffer[2] == 1.0);
+                assert!(buffer[3] == 128.0 / 255.0);
+                if let Ok(QoiDecoderProgress::Finished((buffer, empty, _))) = decoder.process_chunks_linear_f32(&input, [0.0; 4]) {
+                    assert!(empty == 0);
+                    assert!(buffer[0] == SRGB_TO_LINEAR_F32[1] && buffer[1] == SRGB_TO_LINEAR_F32[2]);
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        }
+        input[13] = 1; // colorspace (1 = all channels linear)
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            if let Ok(QoiDecoderProgress::Finished((buffer, _, _))) = decoder.process_chunks_linear_u16(&input, [0; 8]) {
+                assert!(buffer[0] == 32896 && buffer[4] == 257 && buffer[5] == 514 && buffer[6] == 771);
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((decoder, _)) = QoiDecoder::new(&input) {
+            assert!(matches!(decoder.process_chunks_linear_f32(&input, [0.0; 6]), Err(QoiError::IncorrectBufferSize(6))));
+        }
+    }
+    const TRANSPARENT_BLACK: [u8; 23] = [113, 111, 105, 102,      // magic bytes (qoif)
+                                         0, 0, 0, 1,              // width (4xu8 into 1xu32 big endian: 1)
+                                         0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                                         4,                       // channels (4 = RGBA)
+                                         0,                       // colorspace (0 = sRGB with linear alpha)
+                                         0,                       // Index chunk (unset pixel 0, 0, 0, 0)
+                                         0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    #[test]
+    const fn good_process_chunks_alpha_used() {
+        if let Ok((decoder, _)) = QoiDecoder::new(&TRANSPARENT_BLACK) {
+            let progress = decoder.with_stats().process_chunks(&TRANSPARENT_BLACK, [9; 4]);
+            if let Ok(QoiDecoderProgress::Finished((buffer, empty, stats))) = progress {
+                assert!(empty == 0 && is_identical(&buffer, &[0, 0, 0, 0]));
+                assert!(stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn good_validate() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                     3,                       // channels (3 = RGB)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 255, 255, 255,      // RGB chunk
+                     127,                     // Diff chunk (r+1, g+1, b+1)
+                     128, 55,                 // Luma chunk (r-37, g-32, b-33)
+                     38,                      // Index chunk
+                     195,                     // Run chunk (amount 4)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let report = QoiDecoder::validate(&input);
+        assert!(report.is_ok());
+        if let Ok(report) = report {
+            assert!(report.header().width() == 2);
+            assert!(report.header().height() == 4);
+            assert!(report.pixel_amount() == 8);
+            assert!(report.stats().total_chunks() == 5);
+            assert!(report.stats().total_chunk_bytes() == 9);
+            assert!(!report.stats().alpha_used());
+        }
+        let report = QoiDecoder::validate(&TRANSPARENT_BLACK);
+        assert!(matches!(report, Ok(ref report) if report.stats().alpha_used()));
+    }
+    #[test]
+    const fn bad_validate() {
+        let overstated = [113, 111, 105, 102,      // magic bytes (qoif)
+                          0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                          0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                          4,                       // channels (4 = RGBA)
+                          0,                       // colorspace (0 = sRGB with linear alpha)
+                          255, 255, 255, 255, 255, // RGBA chunk
+                                                   // missing chunks: header stated 8 pixels only found 1
+                          0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        assert!(matches!(QoiDecoder::validate(&overstated), Err(QoiError::IncorrectPixelAmount(8, 1))));
+        let into_end = [113, 111, 105, 102,      // magic bytes (qoif)
+                        0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                        0, 0, 0, 2,              // height (4xu8 into 1xu32 big endian: 2)
+                        4,                       // channels (4 = RGBA)
+                        0,                       // colorspace (0 = sRGB with linear alpha)
+                        255, 255, 255, 255, 255, // RGBA chunk
+                        255, 255, 255, 255, 252, // RGBA chunk
+                        255,                     // RGBA chunk (incomplete, should have 4 more bytes)
+                        0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        assert!(matches!(QoiDecoder::validate(&into_end),
+                         Err(QoiError::EndAsChunksUnfinished(1, [255, 255, 255, 252, 255], 4))));
+        let bad_end = [113, 111, 105, 102,      // magic bytes (qoif)
+                       0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                       0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                       4,                       // channels (4 = RGBA)
+                       0,                       // colorspace (0 = sRGB with linear alpha)
+                       255, 255, 255, 255, 255, // RGBA chunk
+                       198,                     // Run chunk (amount 7)
+                       0, 0, 0, 0, 5, 0, 0, 1]; // incorrect end marker
+        assert!(matches!(QoiDecoder::validate(&bad_end), Err(QoiError::InvalidEndMarker(0, 0, 0, 0, 5, 0, 0, 1))));
     }
 }
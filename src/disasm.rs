@@ -0,0 +1,192 @@
+use crate::{
+    consts::{DEFAULT_PIXEL, ZERO_PIXEL},
+    decoder::{check_progress, QoiDecoder},
+    error::QoiError,
+    op::{QoiOp, QoiTokenizer},
+    pixel::Pixel,
+};
+
+/// A human readable disassembly of a QOI image.
+///
+/// The disassembly is produced through [`core::fmt::Display`] so it can be written to anything implementing
+/// [`core::fmt::Write`] without requiring [alloc](https://doc.rust-lang.org/alloc/index.html).
+/// It lists the header followed by every chunk with its offset, values, the resulting pixel with its position and
+/// any change to the previously seen pixels array. Problems with the chunks are listed as errors at the point they
+/// are found.
+///
+/// ```text
+/// header: magic=qoif width=2 height=4 channels=3 colorspace=0
+/// 0x0e: RGB r=255 g=255 b=255 -> px#0 (0,0) rgba(255,255,255,255) index[38]=rgba(255,255,255,255)
+/// 0x12: DIFF dr=+1 dg=+1 db=+1 -> px#1 (1,0) rgba(0,0,0,255) index[53]=rgba(0,0,0,255)
+/// 0x13: LUMA dg=-32 dr-dg=-5 db-dg=-1 -> px#2 (0,1) rgba(219,224,223,255) index[63]=rgba(219,224,223,255)
+/// 0x15: INDEX 38 -> px#3 (1,1) rgba(255,255,255,255)
+/// 0x16: RUN 4 -> px#4..#7 (0,2)..(1,3) rgba(255,255,255,255)
+/// 0x17: END 8 pixels
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub struct QoiDisassembly<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> QoiDisassembly<'a> {
+    /// Generates a [`QoiDisassembly`] from the input bytes of a QOI image.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::new`].
+    pub const fn new(input: &'a [u8]) -> Result<Self, QoiError> {
+        match QoiDecoder::new(input) {
+            Ok(_) => Ok(Self {input}),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl core::fmt::Display for QoiDisassembly<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (tokenizer, header) = match QoiTokenizer::new(self.input) {
+            Ok(both) => both,
+            Err(e) => return writeln!(f, "error: {e}"),
+        };
+        let magic = header.magic_bytes();
+        writeln!(f, "header: magic={}{}{}{} width={} height={} channels={} colorspace={}",
+                 char::from(magic[0]), char::from(magic[1]), char::from(magic[2]), char::from(magic[3]),
+                 header.width(), header.height(), header.channels(), header.colorspace())?;
+        let width = u64::from(header.width());
+        let expected_pixels = header.pixel_amount();
+        let mut seen_pixels = [ZERO_PIXEL; 64];
+        let mut previous_pixel = DEFAULT_PIXEL;
+        let mut pixel_index = 0; // position of the next pixel
+        let mut byte_index = 14;
+        for chunk in tokenizer {
+            let (offset, op) = match chunk {
+                Ok(both) => both,
+                Err(e) => return writeln!(f, "error: {e}"),
+            };
+            write!(f, "{offset:#04x}: ")?;
+            match op {
+                QoiOp::Rgb(red, green, blue) => write!(f, "RGB r={red} g={green} b={blue}")?,
+                QoiOp::Rgba(red, green, blue, alpha) => write!(f, "RGBA r={red} g={green} b={blue} a={alpha}")?,
+                QoiOp::Index(index) => write!(f, "INDEX {index}")?,
+                QoiOp::Diff(red, green, blue) => write!(f, "DIFF dr={red:+} dg={green:+} db={blue:+}")?,
+                QoiOp::Luma(green, red, blue) => write!(f, "LUMA dg={green:+} dr-dg={red:+} db-dg={blue:+}")?,
+                QoiOp::Run(run) => write!(f, "RUN {run}")?,
+            }
+            let pixels = u64::from(op.pixel_amount()).min(expected_pixels - pixel_index);
+            if pixels == 0 {
+                writeln!(f)?;
+                return writeln!(f, "error: {}", QoiError::MoreDataBeforeEnd(expected_pixels, (self.input.len() - 8) - offset));
+            }
+            let pixel = op.apply(previous_pixel, &seen_pixels);
+            let last = pixel_index + pixels - 1;
+            if pixels == 1 {
+                write!(f, " -> px#{pixel_index} ({},{})", pixel_index % width, pixel_index / width)?;
+            } else {
+                write!(f, " -> px#{pixel_index}..#{last} ({},{})..({},{})",
+                       pixel_index % width, pixel_index / width, last % width, last / width)?;
+            }
+            write!(f, " ")?;
+            write_rgba(f, pixel)?;
+            let index = pixel.calculate_hash_index();
+            if !seen_pixels[index].is_same(pixel) {
+                write!(f, " index[{index}]=")?;
+                write_rgba(f, pixel)?;
+                seen_pixels[index] = pixel;
+            }
+            writeln!(f)?;
+            if pixels < u64::from(op.pixel_amount()) { // run chunk continues past the amount of pixels in the header
+                return writeln!(f, "error: {}", QoiError::MoreDataBeforeEnd(expected_pixels, (self.input.len() - 8) - offset));
+            }
+            previous_pixel = pixel;
+            pixel_index += pixels;
+            byte_index = offset + op.size();
+        }
+        match check_progress(self.input, byte_index, expected_pixels - pixel_index, expected_pixels) {
+            Ok(_) => writeln!(f, "{byte_index:#04x}: END {pixel_index} pixels"),
+            Err(e) => writeln!(f, "error: {e}"),
+        }
+    }
+}
+
+fn write_rgba(f: &mut core::fmt::Formatter<'_>, pixel: Pixel) -> core::fmt::Result {
+    write!(f, "rgba({},{},{},{})", pixel.red, pixel.green, pixel.blue, pixel.alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QoiDisassembly;
+    struct Buffer { // fixed size text buffer as the tests cannot allocate
+        bytes: [u8; 1024],
+        len: usize,
+    }
+    impl core::fmt::Write for Buffer {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let end = self.len + s.len();
+            if end > self.bytes.len() {return Err(core::fmt::Error);}
+            self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+    impl Buffer {
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+        }
+    }
+    fn disassemble(input: &[u8]) -> Buffer {
+        use core::fmt::Write;
+        let mut buffer = Buffer {bytes: [0; 1024], len: 0};
+        if let Ok(disassembly) = QoiDisassembly::new(input) {
+            assert!(write!(buffer, "{disassembly}").is_ok());
+        }
+        buffer
+    }
+    #[test]
+    fn good_disassembly() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                     3,                       // channels (3 = RGB)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 255, 255, 255,      // RGB chunk
+                     127,                     // Diff chunk (r+1, g+1, b+1)
+                     128, 55,                 // Luma chunk (r-37, g-32, b-33)
+                     38,                      // Index chunk
+                     195,                     // Run chunk (amount 4)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let expected = "header: magic=qoif width=2 height=4 channels=3 colorspace=0\n\
+                        0x0e: RGB r=255 g=255 b=255 -> px#0 (0,0) rgba(255,255,255,255) index[38]=rgba(255,255,255,255)\n\
+                        0x12: DIFF dr=+1 dg=+1 db=+1 -> px#1 (1,0) rgba(0,0,0,255) index[53]=rgba(0,0,0,255)\n\
+                        0x13: LUMA dg=-32 dr-dg=-5 db-dg=-1 -> px#2 (0,1) rgba(219,224,223,255) index[63]=rgba(219,224,223,255)\n\
+                        0x15: INDEX 38 -> px#3 (1,1) rgba(255,255,255,255)\n\
+                        0x16: RUN 4 -> px#4..#7 (0,2)..(1,3) rgba(255,255,255,255)\n\
+                        0x17: END 8 pixels\n";
+        assert_eq!(disassemble(&input).as_str(), expected);
+    }
+    #[test]
+    fn bad_disassembly() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     198,                     // Run chunk (amount 7, header only specifies 2 pixels)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let expected = "header: magic=qoif width=2 height=1 channels=4 colorspace=0\n\
+                        0x0e: RUN 7 -> px#0..#1 (0,0)..(1,0) rgba(0,0,0,255) index[53]=rgba(0,0,0,255)\n\
+                        error: Malformed input: header specified 2 pixels but found 1 chunk bytes left to process from input before 8 byte end marker\n";
+        assert_eq!(disassemble(&input).as_str(), expected);
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     192,                     // Run chunk (amount 1)
+                     255, 255,                // RGBA chunk (incomplete, should have 3 more bytes)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let expected = "header: magic=qoif width=2 height=1 channels=4 colorspace=0\n\
+                        0x0e: RUN 1 -> px#0 (0,0) rgba(0,0,0,255) index[53]=rgba(0,0,0,255)\n\
+                        error: Malformed input: the chunk at offset 15 is incomplete and reads into the 8 byte end marker\n";
+        assert_eq!(disassemble(&input).as_str(), expected);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    consts::END_MARKER,
+    decoder::{QoiDecoder, QoiDecoderProgress},
+    encoder::{QoiEncoder, QoiEncoderProgress},
+    error::QoiError,
+};
+
+/// Decodes a complete QOI image into an array of bytes representing RGBA pixel values.
+///
+/// This is intended for use in `const` and `static` items so images can be decoded at compile time.
+/// The size of the array must be exactly the pixel amount of the image multiplied by `4`.
+/// The [`include_qoi`](crate::include_qoi) macro calculates the size from the header for you.
+///
+/// # Errors
+///
+/// Will return `Err` if the size of the array does not match the image or the input is malformed.
+/// See [`QoiDecoder::new`] and [`QoiDecoder::process_chunks`] for the possible errors.
+pub const fn decode_to_array<const N: usize>(input: &[u8]) -> Result<[u8; N], QoiError> {
+    let decoder = match QoiDecoder::new(input) {
+        Ok((decoder, header)) => {
+            match header.decoded_size(4) {
+                Some(size) if size == N => decoder,
+                _ => return Err(QoiError::ArraySizeMismatch(N, header.pixel_amount())),
+            }
+        },
+        Err(e) => return Err(e),
+    };
+    match decoder.process_chunks(input, [0; N]) {
+        Ok(QoiDecoderProgress::Finished((output, _, _))) => Ok(output),
+        Ok(QoiDecoderProgress::Unfinished(_)) => unreachable!(), // array holds every pixel of the image
+        Err(e) => Err(e),
+    }
+}
+
+/// Calculates the exact size in bytes of the QOI image produced by encoding the input bytes of pixel data.
+///
+/// The size includes the `14` byte header and the `8` byte end marker.
+/// This can be used in a `const` item to size the array for [`encode_to_array`].
+///
+/// # Errors
+///
+/// Will return `Err` under the same conditions as [`QoiEncoder::new`].
+pub const fn encoded_size(input: &[u8], width: u32, height: u32, channels: u8, colorspace: u8) -> Result<usize, QoiError> {
+    match QoiEncoder::new(input, width, height, channels, colorspace) {
+        Ok((encoder, _)) => Ok(encoder.encoded_size(input)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Encodes the input bytes of pixel data into an array containing a complete QOI image.
+///
+/// The array will contain the `14` byte header, the QOI data chunks and the `8` byte end marker.
+/// This is intended for use in `const` and `static` items so images can be encoded at compile time.
+/// The size of the array must be exactly the size calculated by [`encoded_size`].
+/// The [`encode_qoi`](crate::encode_qoi) macro calculates the size for you.
+///
+/// # Errors
+///
+/// Will return `Err` if the size of the array does not match the encoded image size or under the same conditions as
+/// [`QoiEncoder::new`].
+pub const fn encode_to_array<const N: usize>(input: &[u8],
+                                             width: u32,
+                                             height: u32,
+                                             channels: u8,
+                                             colorspace: u8) -> Result<[u8; N], QoiError> {
+    let (mut encoder, header) = match QoiEncoder::new(input, width, height, channels, colorspace) {
+        Ok(both) => both,
+        Err(e) => return Err(e),
+    };
+    let size = encoder.encoded_size(input);
+    if size != N {return Err(QoiError::EncodedSizeMismatch(N, size));}
+    let (mut output, mut output_index) = append([0; N], 0, &header.to_u8());
+    loop {
+        match encoder.process_pixels(input, [0; 64]) {
+            Ok(QoiEncoderProgress::Unfinished(enc, buffer, empty)) => {
+                encoder = enc;
+                (output, output_index) = append(output, output_index, buffer.split_at(buffer.len() - empty).0);
+            },
+            Ok(QoiEncoderProgress::Finished(buffer, empty, _)) => {
+                (output, output_index) = append(output, output_index, buffer.split_at(buffer.len() - empty).0);
+                break;
+            },
+            Err(e) => return Err(e),
+        }
+    }
+    (output, _) = append(output, output_index, &END_MARKER);
+    Ok(output)
+}
+
+// copies all of the input into the output starting at the output index. panics if output is too small
+const fn append<const N: usize>(mut output: [u8; N], mut output_index: usize, input: &[u8]) -> ([u8; N], usize) {
+    let mut input_index = 0;
+    while input_index != input.len() {
+        output[output_index] = input[input_index];
+        output_index += 1;
+        input_index += 1;
+    }
+    (output, output_index)
+}
+
+/// Decodes a QOI image at compile time into a `const` or `static` array of bytes representing RGBA pixel values.
+///
+/// The size of the array is calculated from the header so it does not need to be written out.
+/// Compilation fails if the input is not a valid QOI image.
+///
+/// ```
+/// # const LOGO_QOI: [u8; 28] = [113, 111, 105, 102, 0, 0, 0, 2, 0, 0, 0, 4, 4, 0, 255, 255, 255, 255, 255, 198,
+/// #                             0, 0, 0, 0, 0, 0, 0, 1];
+/// // static LOGO: [u8; 2 * 4 * 4] = ...
+/// const_qoi::include_qoi!(static LOGO = &LOGO_QOI);
+/// // or with a file: const_qoi::include_qoi!(pub static LOGO = include_bytes!("logo.qoi"));
+/// assert_eq!(LOGO, [255; 32]);
+/// ```
+#[macro_export]
+macro_rules! include_qoi {
+    ($(#[$attr:meta])* $vis:vis static $name:ident = $input:expr $(;)?) => {
+        $(#[$attr])* $vis static $name: [u8; $crate::include_qoi!(@size $input)] = $crate::include_qoi!(@decode $input);
+    };
+    ($(#[$attr:meta])* $vis:vis const $name:ident = $input:expr $(;)?) => {
+        $(#[$attr])* $vis const $name: [u8; $crate::include_qoi!(@size $input)] = $crate::include_qoi!(@decode $input);
+    };
+    (@size $input:expr) => {
+        match $crate::QoiDecoder::new($input) {
+            Ok((_, header)) => match header.decoded_size(4) {
+                Some(size) => size,
+                None => panic!("decoded QOI image is too large for the target architecture"),
+            },
+            Err(_) => panic!("invalid QOI header or end marker"),
+        }
+    };
+    (@decode $input:expr) => {
+        match $crate::decode_to_array($input) {
+            Ok(output) => output,
+            Err(_) => panic!("malformed QOI data chunks"),
+        }
+    };
+}
+
+/// Encodes pixel data at compile time into a `const` or `static` array containing a complete QOI image.
+///
+/// The size of the array is calculated by [`encoded_size`](crate::encoded_size) so it does not need to be written out.
+/// Compilation fails if the pixel data does not match the width, height, channels and colorspace values.
+///
+/// ```
+/// const PIXELS: [u8; 16] = [0, 0, 0, 0, 1, 1, 1, 0, 255, 255, 255, 255, 255, 255, 255, 255];
+/// // static IMAGE: [u8; 14 + 8 + 8] = ...
+/// const_qoi::encode_qoi!(static IMAGE = &PIXELS, 2, 2, 4, 0);
+/// assert_eq!(IMAGE.len(), 14 + 8 + 8);
+/// ```
+#[macro_export]
+macro_rules! encode_qoi {
+    ($(#[$attr:meta])* $vis:vis static $name:ident = $input:expr, $width:expr, $height:expr, $channels:expr, $colorspace:expr $(,)? $(;)?) => {
+        $(#[$attr])* $vis static $name: [u8; $crate::encode_qoi!(@size $input, $width, $height, $channels, $colorspace)] =
+            $crate::encode_qoi!(@encode $input, $width, $height, $channels, $colorspace);
+    };
+    ($(#[$attr:meta])* $vis:vis const $name:ident = $input:expr, $width:expr, $height:expr, $channels:expr, $colorspace:expr $(,)? $(;)?) => {
+        $(#[$attr])* $vis const $name: [u8; $crate::encode_qoi!(@size $input, $width, $height, $channels, $colorspace)] =
+            $crate::encode_qoi!(@encode $input, $width, $height, $channels, $colorspace);
+    };
+    (@size $input:expr, $width:expr, $height:expr, $channels:expr, $colorspace:expr) => {
+        match $crate::encoded_size($input, $width, $height, $channels, $colorspace) {
+            Ok(size) => size,
+            Err(_) => panic!("pixel data does not match the width, height, channels and colorspace values"),
+        }
+    };
+    (@encode $input:expr, $width:expr, $height:expr, $channels:expr, $colorspace:expr) => {
+        match $crate::encode_to_array($input, $width, $height, $channels, $colorspace) {
+            Ok(output) => output,
+            Err(_) => panic!("pixel data could not be encoded"),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::QoiError, utils::is_identical};
+    use super::{decode_to_array, encode_to_array, encoded_size};
+    const INPUT: [u8; 28] = [113, 111, 105, 102,      // magic bytes (qoif)
+                             0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                             0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                             4,                       // channels (4 = RGBA)
+                             0,                       // colorspace (0 = sRGB with linear alpha)
+                             255, 255, 255, 255, 255, // RGBA chunk
+                             198,                     // Run chunk (amount 7)
+                             0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    crate::include_qoi!(const INCLUDED = &INPUT);
+    const PIXELS: [u8; 16] = [0, 0, 0, 0,          // encoded as index chunk [0]
+                              1, 1, 1, 0,          // encoded as diff chunk  [127]
+                              255, 255, 255, 255,  // encoded as rgba chunk  [255, 255, 255, 255, 255]
+                              255, 255, 255, 255]; // encoded as run chunk   [192]
+    const ENCODED: [u8; 30] = [113, 111, 105, 102,      // magic bytes (qoif)
+                               0, 0, 0, 2,              // width
+                               0, 0, 0, 2,              // height
+                               4,                       // channels
+                               0,                       // colorspace
+                               0,                       // index chunk
+                               127,                     // diff chunk
+                               255, 255, 255, 255, 255, // rgba chunk
+                               192,                     // run chunk
+                               0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    crate::encode_qoi!(const EMBEDDED = &PIXELS, 2, 2, 4, 0);
+    #[test]
+    const fn good_decode_to_array() {
+        let output = decode_to_array::<32>(&INPUT);
+        assert!(output.is_ok());
+        if let Ok(output) = output {
+            assert!(is_identical(&output, &[255; 32]));
+        }
+    }
+    #[test]
+    const fn good_include_qoi() {
+        assert!(INCLUDED.len() == 32);
+        assert!(is_identical(&INCLUDED, &[255; 32]));
+    }
+    #[test]
+    const fn good_encoded_size() {
+        assert!(matches!(encoded_size(&PIXELS, 2, 2, 4, 0), Ok(30)));
+        assert!(matches!(encoded_size(&PIXELS, 2, 3, 4, 0), Err(QoiError::InputHeaderMismatch(2, 3, 4))));
+    }
+    #[test]
+    const fn good_encode_to_array() {
+        let output = encode_to_array::<30>(&PIXELS, 2, 2, 4, 0);
+        assert!(output.is_ok());
+        if let Ok(output) = output {
+            assert!(is_identical(&output, &ENCODED));
+        }
+    }
+    #[test]
+    const fn good_encode_qoi() {
+        assert!(EMBEDDED.len() == 30);
+        assert!(is_identical(&EMBEDDED, &ENCODED));
+    }
+    #[test]
+    const fn good_round_trip() {
+        let output = decode_to_array::<16>(&ENCODED);
+        assert!(output.is_ok());
+        if let Ok(output) = output {
+            assert!(is_identical(&output, &PIXELS));
+        }
+    }
+    #[test]
+    const fn bad_encoded_size() {
+        let output = encode_to_array::<31>(&PIXELS, 2, 2, 4, 0);
+        assert!(output.is_err());
+        if let Err(e) = output {
+            match e {
+                QoiError::EncodedSizeMismatch(size, encoded) => {
+                    assert!(size == 31);
+                    assert!(encoded == 30);
+                },
+                _ => unreachable!(),
+            }
+        }
+    }
+    #[test]
+    const fn bad_array_size() {
+        let output = decode_to_array::<28>(&INPUT);
+        assert!(output.is_err());
+        if let Err(e) = output {
+            match e {
+                QoiError::ArraySizeMismatch(size, pixels) => {
+                    assert!(size == 28);
+                    assert!(pixels == 8);
+                },
+                _ => unreachable!(),
+            }
+        }
+    }
+}
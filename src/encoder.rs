@@ -1,8 +1,14 @@
 use crate::{
+    color::{
+        f32_to_u8, linear_f32_to_srgb, linear_to_srgb, reduce_f32, reduce_u16, u16_to_u8, unpremultiply, yuv_to_rgb,
+        ColorKeys, QoiPremultiply, QoiReduction, QoiYuvLayout, QoiYuvMatrix,
+    },
     consts::{DEFAULT_PIXEL, ZERO_PIXEL},
     error::QoiError,
     header::{QoiHeader, QoiHeaderInternal},
+    op::QoiOp,
     pixel::Pixel,
+    stats::{QoiStats, DIFF, INDEX, LUMA, RGB, RGBA, RUN},
 };
 
 /// Indicates whether the [`QoiEncoder`] is finished.
@@ -14,8 +20,23 @@ pub enum QoiEncoderProgress<const N: usize> {
     /// The largest chunk is `5` bytes which is the minimum allowed output buffer size.
     /// Due to the different size chunks the buffer may not always be returned full.
     Unfinished(QoiEncoder, [u8; N], usize),
-    /// Returns the output buffer and the amount of bytes that should be considered as free space.
-    Finished([u8; N], usize),
+    /// Returns the output buffer, the amount of bytes that should be considered as free space and the [`QoiStats`].
+    Finished([u8; N], usize, QoiStats),
+}
+
+/// How the [`QoiEncoder`] treats fully transparent pixels (alpha `0`) before choosing chunks.
+///
+/// The colour of a fully transparent pixel is invisible but still has to be encoded.
+/// Image editors often leave random colours behind which wastes bytes and breaks up runs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiTransparentPixels {
+    /// Keeps the colour of transparent pixels unchanged. This is the default.
+    Keep,
+    /// Replaces every transparent pixel with `0, 0, 0, 0`.
+    Zero,
+    /// Replaces every transparent pixel with the previous transparent pixel.
+    /// The first transparent pixel is kept unchanged.
+    Previous,
 }
 
 /// A streaming encoder for the QOI image format.
@@ -27,6 +48,7 @@ pub enum QoiEncoderProgress<const N: usize> {
 #[allow(clippy::module_name_repetitions)]
 pub struct QoiEncoder {
     state: QoiEncoderInternal,
+    collect_stats: bool, // whether to count the generated chunks
 }
 
 impl QoiEncoder {
@@ -54,21 +76,312 @@ impl QoiEncoder {
                      height: u32,
                      channels: u8,
                      colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        Self::new_with_len(input.len(), width, height, channels, colorspace)
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from input of 16 bit pixel values.
+    ///
+    /// The input is processed with [`QoiEncoder::process_pixels_u16`] or [`QoiEncoder::process_pixels_linear_u16`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::new`] with each value counting as a byte.
+    pub const fn new_u16(input: &[u16],
+                         width: u32,
+                         height: u32,
+                         channels: u8,
+                         colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        Self::new_with_len(input.len(), width, height, channels, colorspace)
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from input of floating point pixel values.
+    ///
+    /// The input is processed with [`QoiEncoder::process_pixels_f32`] or [`QoiEncoder::process_pixels_linear_f32`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::new`] with each value counting as a byte.
+    pub const fn new_f32(input: &[f32],
+                         width: u32,
+                         height: u32,
+                         channels: u8,
+                         colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        Self::new_with_len(input.len(), width, height, channels, colorspace)
+    }
+    const fn new_with_len(input_len: usize,
+                          width: u32,
+                          height: u32,
+                          channels: u8,
+                          colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
         if width == 0 || height == 0 {return Err(QoiError::InvalidWidthHeight(width, height));}
         if channels != 3 && channels != 4 {return Err(QoiError::InvalidChannelsValue(channels));}
         if colorspace != 0 && colorspace != 1 {return Err(QoiError::InvalidColorspaceValue(colorspace));}
         let specified_pixel_amount = width as u64 * height as u64;
-        if input.len() % (channels as usize) != 0 {return Err(QoiError::IncorrectInputData(input.len(), channels));}
-        let three = (input.len() as u64) / 3;
-        let four = (input.len() as u64) / 4;
+        if input_len % (channels as usize) != 0 {return Err(QoiError::IncorrectInputData(input_len, channels));}
+        let three = (input_len as u64) / 3;
+        let four = (input_len as u64) / 4;
         let (actual_pixel_amount, real_channels) = if three == specified_pixel_amount {(three, 3)} else {(four, 4)};
         if specified_pixel_amount != three && specified_pixel_amount != four {
             return Err(QoiError::InputHeaderMismatch(width, height, actual_pixel_amount));
         }
         let header = QoiHeaderInternal::new(width, height, channels, colorspace);
-        let encoder = QoiEncoder {state: QoiEncoderInternal::new(specified_pixel_amount, real_channels != 3)};
+        let state = QoiEncoderInternal::new(specified_pixel_amount, real_channels != 3, colorspace, width);
+        let encoder = QoiEncoder {state, collect_stats: false};
         Ok((encoder, header.public()))
     }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from the input bytes of pixel data, detecting the channels value.
+    ///
+    /// Input of `4` byte pixels (RGBA) is scanned and the returned header will have a channels value of `3` (RGB) when
+    /// every alpha value is `255`.
+    /// Input of `3` byte pixels (RGB) always results in a channels value of `3` (RGB).
+    /// The input is processed the same way as for [`QoiEncoder::new`] so only the header is affected.
+    ///
+    /// When the header must be written before all of the input is available use [`QoiEncoder::new`] instead and check
+    /// [`QoiStats::alpha_used`] when finished with [`QoiEncoder::with_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).\
+    /// 3: The amount of bytes in input are not divisible by `3` when they do not represent `4` byte pixels.\
+    /// 4: The specified width and height calculate to a different amount of pixels compared to the input bytes.
+    pub const fn new_detect_channels(input: &[u8],
+                                     width: u32,
+                                     height: u32,
+                                     colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        let pixel_amount = width as u64 * height as u64;
+        let channels = if input.len() % 4 == 0 && input.len() as u64 / 4 == pixel_amount {4} else {3};
+        let (encoder, header) = match Self::new(input, width, height, channels, colorspace) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        if channels == 3 {return Ok((encoder, header));}
+        let mut byte_index = 3;
+        while byte_index < input.len() {
+            if input[byte_index] != 255 {return Ok((encoder, header));}
+            byte_index += 4;
+        }
+        Ok((encoder, QoiHeaderInternal::new(width, height, 3, colorspace).public()))
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from the input bytes of pixel data, replacing colour keys with
+    /// fully transparent pixels.
+    ///
+    /// Every input pixel whose red, green and blue values are each within `tolerance` of one of the `keys` gets an
+    /// alpha value of `0` while keeping its colour. The returned header always has a channels value of `4` (RGBA).
+    /// Input of `3` byte pixels (RGB) and `4` byte pixels (RGBA) is detected the same way as for
+    /// [`QoiEncoder::new_detect_channels`].
+    /// Use [`QoiEncoder::with_transparent_pixels`] to also replace the colour of the keyed pixels and
+    /// [`QoiDecoder::with_color_key`](crate::QoiDecoder::with_color_key) to restore a key colour when decoding.
+    /// The amount of keyed pixels is returned in [`QoiStats::changed_pixels`] when finished with
+    /// [`QoiEncoder::with_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).\
+    /// 3: The amount of bytes in input are not divisible by `3` when they do not represent `4` byte pixels.\
+    /// 4: The specified width and height calculate to a different amount of pixels compared to the input bytes.\
+    /// 5: The amount of keys is `0` or more than `8`.
+    pub const fn new_color_keyed(input: &[u8],
+                                 width: u32,
+                                 height: u32,
+                                 colorspace: u8,
+                                 keys: &[[u8; 3]],
+                                 tolerance: u8) -> Result<(Self, QoiHeader), QoiError> {
+        let Some(color_keys) = ColorKeys::new(keys, tolerance) else {
+            return Err(QoiError::InvalidColorKeyAmount(keys.len()));
+        };
+        let pixel_amount = width as u64 * height as u64;
+        let channels = if input.len() % 4 == 0 && input.len() as u64 / 4 == pixel_amount {4} else {3};
+        let mut encoder = match Self::new_with_len(input.len(), width, height, channels, colorspace) {
+            Ok((encoder, _)) => encoder,
+            Err(e) => return Err(e),
+        };
+        encoder.state.color_keys = Some(color_keys);
+        Ok((encoder, QoiHeaderInternal::new(width, height, 4, colorspace).public()))
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from palette indices.
+    ///
+    /// Each index is `bits` wide (`1`, `2`, `4` or `8`) and refers to an RGBA entry of the palette.
+    /// Indices smaller than a byte are packed with the leftmost pixel in the most significant bits.
+    /// Every row starts at a multiple of `stride` bytes, a stride of `0` means the rows are packed without padding.
+    /// The returned header has a channels value of `3` (RGB) when every palette entry has an alpha value of `255`.
+    /// Every index is checked so the colours can be looked up while processing without building a buffer of pixels.
+    ///
+    /// The input is processed with [`QoiEncoder::process_pixels_indexed`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).\
+    /// 3: The palette has no entries or more than `256` entries.\
+    /// 4: The bits per index are not `1`, `2`, `4` or `8`.\
+    /// 5: The stride is smaller than the bytes required by a row of indices.\
+    /// 6: The specified width and height calculate to more pixels than the indices contain.\
+    /// 7: An index is outside of the palette.
+    pub const fn new_indexed(indices: &[u8],
+                             palette: &[[u8; 4]],
+                             bits: u8,
+                             stride: usize,
+                             width: u32,
+                             height: u32,
+                             colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        if width == 0 || height == 0 {return Err(QoiError::InvalidWidthHeight(width, height));}
+        if colorspace != 0 && colorspace != 1 {return Err(QoiError::InvalidColorspaceValue(colorspace));}
+        if palette.is_empty() || palette.len() > 256 {return Err(QoiError::InvalidPaletteSize(palette.len()));}
+        if bits != 1 && bits != 2 && bits != 4 && bits != 8 {return Err(QoiError::InvalidIndexBits(bits));}
+        let row_bytes = (width as usize * bits as usize).div_ceil(8);
+        let stride = if stride == 0 {row_bytes} else {stride};
+        if stride < row_bytes {return Err(QoiError::InvalidStride(stride, row_bytes));}
+        let rows = if indices.len() < row_bytes {0} else {(indices.len() - row_bytes) / stride + 1};
+        if (rows as u64) < height as u64 {
+            return Err(QoiError::InputHeaderMismatch(width, height, rows as u64 * width as u64));
+        }
+        let mut alpha = false;
+        let mut entry = 0;
+        while entry < palette.len() {
+            if palette[entry][3] != 255 {alpha = true;}
+            entry += 1;
+        }
+        let pixel_amount = width as u64 * height as u64;
+        let mut state = QoiEncoderInternal::new(pixel_amount, alpha, colorspace, width);
+        state.index_bits = bits;
+        state.index_stride = stride;
+        let mut position = 0;
+        while position < pixel_amount {
+            let index = state.palette_index(indices, position);
+            if index as usize >= palette.len() {return Err(QoiError::PaletteIndexOutOfRange(index, position));}
+            position += 1;
+        }
+        let header = QoiHeaderInternal::new(width, height, if alpha {4} else {3}, colorspace);
+        Ok((QoiEncoder {state, collect_stats: false}, header.public()))
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from a YCbCr (YUV) frame.
+    ///
+    /// Every pixel is converted to RGB while processing without building a buffer of pixels.
+    /// Pixels sharing chroma values all use the same values.
+    /// The returned header has a channels value of `3` (RGB).
+    ///
+    /// The input is processed with [`QoiEncoder::process_pixels_yuv`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).\
+    /// 3: The amount of bytes in input are not the size of a frame with the specified width, height and layout.
+    pub const fn new_yuv(input: &[u8],
+                         width: u32,
+                         height: u32,
+                         layout: QoiYuvLayout,
+                         matrix: QoiYuvMatrix,
+                         colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        if width == 0 || height == 0 {return Err(QoiError::InvalidWidthHeight(width, height));}
+        if colorspace != 0 && colorspace != 1 {return Err(QoiError::InvalidColorspaceValue(colorspace));}
+        let frame_size = layout.frame_size(width, height);
+        if input.len() as u64 != frame_size {return Err(QoiError::IncorrectFrameSize(input.len(), frame_size));}
+        let mut state = QoiEncoderInternal::new(width as u64 * height as u64, false, colorspace, width);
+        state.yuv = (layout, matrix);
+        let header = QoiHeaderInternal::new(width, height, 3, colorspace);
+        Ok((QoiEncoder {state, collect_stats: false}, header.public()))
+    }
+    /// Generates a [`QoiEncoder`] and a [`QoiHeader`] from separate red, green, blue and optional alpha planes.
+    ///
+    /// Every plane holds `1` byte per pixel and every row of a plane starts at a multiple of its stride in bytes, a
+    /// stride of `0` means the rows are packed without padding.
+    /// The returned header has a channels value of `4` (RGBA) when `4` planes are given and `3` (RGB) otherwise.
+    ///
+    /// The input is processed with [`QoiEncoder::process_pixels_planar`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).\
+    /// 3: The amount of planes is not `3` (RGB) or `4` (RGBA).\
+    /// 4: A stride is smaller than the width.\
+    /// 5: A plane is smaller than the rows required by the height.
+    pub const fn new_planar<const P: usize>(planes: [&[u8]; P],
+                                            strides: [usize; P],
+                                            width: u32,
+                                            height: u32,
+                                            colorspace: u8) -> Result<(Self, QoiHeader), QoiError> {
+        if width == 0 || height == 0 {return Err(QoiError::InvalidWidthHeight(width, height));}
+        if colorspace != 0 && colorspace != 1 {return Err(QoiError::InvalidColorspaceValue(colorspace));}
+        if P != 3 && P != 4 {return Err(QoiError::InvalidPlaneAmount(P));}
+        let mut state = QoiEncoderInternal::new(width as u64 * height as u64, P == 4, colorspace, width);
+        let mut plane = 0;
+        while plane < P {
+            let stride = if strides[plane] == 0 {width as usize} else {strides[plane]};
+            if stride < width as usize {return Err(QoiError::InvalidStride(stride, width as usize));}
+            let required = (height as usize - 1) * stride + width as usize;
+            if planes[plane].len() < required {
+                return Err(QoiError::PlaneTooSmall(plane, planes[plane].len(), required));
+            }
+            state.plane_strides[plane] = stride;
+            plane += 1;
+        }
+        let header = QoiHeaderInternal::new(width, height, if P == 4 {4} else {3}, colorspace);
+        Ok((QoiEncoder {state, collect_stats: false}, header.public()))
+    }
+    /// Enables collecting [`QoiStats`] about the generated chunks which are returned when finished.
+    ///
+    /// Collecting statistics is optional as it adds a small amount of work for every generated chunk.
+    /// Without it the returned [`QoiStats`] are empty.
+    #[must_use]
+    pub const fn with_stats(mut self) -> Self {
+        self.collect_stats = true;
+        self
+    }
+    /// Guarantees the generated QOI data chunks are byte for byte identical to the reference `qoi.h` encoder.
+    ///
+    /// By default a run chunk also adds its pixel to the seen pixels like the decoder does.
+    /// This only matters when the image starts with a run of `0, 0, 0, 255` pixels as that pixel can then be encoded
+    /// as an index chunk later on.
+    /// The reference encoder never adds the pixel of a run chunk so it uses a larger chunk instead.
+    /// Both outputs decode to the same pixels but only this mode is suitable when the encoded bytes must be stable
+    /// across encoders, such as when they are hashed to identify an image.
+    #[must_use]
+    pub const fn with_reference_parity(mut self) -> Self {
+        self.state.reference_parity = true;
+        self
+    }
+    /// Changes the colour of fully transparent pixels (alpha `0`) before choosing chunks.
+    ///
+    /// Only `4` byte pixels (RGBA) and pixels matching a key of [`QoiEncoder::new_color_keyed`] can be transparent.
+    /// The decoded image will not be identical to the input but every pixel will look the same.
+    /// The amount of changed pixels is returned in [`QoiStats::changed_pixels`] when finished with
+    /// [`QoiEncoder::with_stats`].
+    #[must_use]
+    pub const fn with_transparent_pixels(mut self, transparent_pixels: QoiTransparentPixels) -> Self {
+        self.state.transparent_pixels = transparent_pixels;
+        self
+    }
+    /// Accepts input pixels with premultiplied alpha and divides their colour values by alpha before choosing chunks.
+    ///
+    /// QOI images store straight alpha so premultiplied pixels must be converted with correct rounding.
+    /// The colorspace value decides whether [`QoiPremultiply::LinearLight`] converts to linear light.
+    /// Only `4` byte pixels (RGBA) are affected and the colour of fully transparent pixels becomes `0, 0, 0`.
+    #[must_use]
+    pub const fn with_premultiplied_alpha(mut self, premultiply: QoiPremultiply) -> Self {
+        self.state.premultiply = Some(premultiply);
+        self
+    }
+    /// Chooses how [`QoiEncoder::process_pixels_u16`] and [`QoiEncoder::process_pixels_f32`] reduce their input to
+    /// 8 bit values.
+    ///
+    /// The default is [`QoiReduction::Round`].
+    #[must_use]
+    pub const fn with_reduction(mut self, reduction: QoiReduction) -> Self {
+        self.state.reduction = reduction;
+        self
+    }
     /// Processes the input bytes as pixel data and fills the output buffer with bytes representing QOI data chunks.
     ///
     /// The minimum size buffer required is `5` bytes.
@@ -78,25 +391,204 @@ impl QoiEncoder {
     ///
     /// Will return `Err` if output buffer is less than `5` bytes.
     #[inline]
-    pub const fn process_pixels<const N: usize>(mut self,
+    pub const fn process_pixels<const N: usize>(self,
                                                 input: &[u8],
                                                 output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::Bytes(input), output)
+    }
+    /// Processes the input as 16 bit RGB or RGBA values (`0..=65535`) and fills the output buffer with bytes
+    /// representing QOI data chunks.
+    ///
+    /// The values must already match the colorspace value and are reduced to 8 bits as chosen with
+    /// [`QoiEncoder::with_reduction`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_u16<const N: usize>(self,
+                                                    input: &[u16],
+                                                    output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::U16(input), output)
+    }
+    /// Processes the input as floating point RGB or RGBA values (`0.0..=1.0`) and fills the output buffer with bytes
+    /// representing QOI data chunks.
+    ///
+    /// The values must already match the colorspace value and are reduced to 8 bits as chosen with
+    /// [`QoiEncoder::with_reduction`].
+    /// Values below `0.0` become `0`, values above `1.0` become `255` and NaN becomes `0`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_f32<const N: usize>(self,
+                                                    input: &[f32],
+                                                    output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::F32(input), output)
+    }
+    /// Processes the input as linear light RGB or RGBA values (`0..=65535`) and fills the output buffer with bytes
+    /// representing QOI data chunks.
+    ///
+    /// Colour values are converted to 8 bit sRGB with correct rounding when the colorspace value is `0` (sRGB with
+    /// linear alpha) using a lookup table built at compile time.
+    /// Alpha values and colour values for a colorspace value of `1` (all channels linear) are rounded to 8 bits.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_linear_u16<const N: usize>(self,
+                                                           input: &[u16],
+                                                           output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::LinearU16(input), output)
+    }
+    /// Processes the input as linear light RGB or RGBA values (`0.0..=1.0`) and fills the output buffer with bytes
+    /// representing QOI data chunks.
+    ///
+    /// Colour values are converted to 8 bit sRGB with correct rounding when the colorspace value is `0` (sRGB with
+    /// linear alpha) using a lookup table built at compile time.
+    /// Alpha values and colour values for a colorspace value of `1` (all channels linear) are rounded to 8 bits.
+    /// Values below `0.0` become `0`, values above `1.0` become `255` and NaN becomes `0`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_linear_f32<const N: usize>(self,
+                                                           input: &[f32],
+                                                           output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::LinearF32(input), output)
+    }
+    /// Processes palette indices and fills the output buffer with bytes representing QOI data chunks.
+    ///
+    /// The indices and palette must be the same as given to [`QoiEncoder::new_indexed`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_indexed<const N: usize>(self,
+                                                        indices: &[u8],
+                                                        palette: &[[u8; 4]],
+                                                        output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::Indexed(indices, palette), output)
+    }
+    /// Processes a YCbCr (YUV) frame and fills the output buffer with bytes representing QOI data chunks.
+    ///
+    /// The frame must be the same as given to [`QoiEncoder::new_yuv`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_yuv<const N: usize>(self,
+                                                    input: &[u8],
+                                                    output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::Yuv(input), output)
+    }
+    /// Processes separate planes and fills the output buffer with bytes representing QOI data chunks.
+    ///
+    /// The planes must be the same as given to [`QoiEncoder::new_planar`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiEncoder::process_pixels`].
+    pub const fn process_pixels_planar<const N: usize, const P: usize>(self,
+                                                                       planes: [&[u8]; P],
+                                                                       output: [u8; N])
+                                                                       -> Result<QoiEncoderProgress<N>, QoiError> {
+        self.process(PixelInput::Planar(&planes), output)
+    }
+    #[inline]
+    const fn process<const N: usize>(mut self,
+                                     input: PixelInput,
+                                     output: [u8; N]) -> Result<QoiEncoderProgress<N>, QoiError> {
         if output.len() < 5 {return Err(QoiError::BufferTooSmall(output.len()));}
-        let (encoder, output) = self.state.process_pixels(input, output);
+        let (encoder, output) = if self.collect_stats {
+            self.state.process_pixels::<N, true>(input, output)
+        } else {
+            self.state.process_pixels::<N, false>(input, output)
+        };
         self.state = encoder;
         let empty = self.state.output_buffer_space;
         if self.all_pixels_processed() {
-            Ok(QoiEncoderProgress::Finished(output, empty))
+            Ok(QoiEncoderProgress::Finished(output, empty, self.state.stats))
         } else {
             Ok(QoiEncoderProgress::Unfinished(self, output, empty))
         }
     }
+    /// Calculates the exact size in bytes of the QOI image the encoder would produce from the input bytes of pixel data.
+    ///
+    /// The size includes the `14` byte header and the `8` byte end marker.
+    /// No output buffer is required as the encoding is performed without keeping the generated QOI data chunks.
+    /// The encoder is left untouched so it can still be used to encode the input afterwards.
+    ///
+    /// If the encoder has already processed some of the input only the remaining pixels will contribute to the size.
+    /// Each way of processing input has a matching size calculation which must be given the same input.
+    #[must_use]
+    pub const fn encoded_size(&self, input: &[u8]) -> usize {
+        self.size(PixelInput::Bytes(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_u16`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_u16(&self, input: &[u16]) -> usize {
+        self.size(PixelInput::U16(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_f32`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_f32(&self, input: &[f32]) -> usize {
+        self.size(PixelInput::F32(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_linear_u16`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_linear_u16(&self, input: &[u16]) -> usize {
+        self.size(PixelInput::LinearU16(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_linear_f32`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_linear_f32(&self, input: &[f32]) -> usize {
+        self.size(PixelInput::LinearF32(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_indexed`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_indexed(&self, indices: &[u8], palette: &[[u8; 4]]) -> usize {
+        self.size(PixelInput::Indexed(indices, palette))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_yuv`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_yuv(&self, input: &[u8]) -> usize {
+        self.size(PixelInput::Yuv(input))
+    }
+    /// Calculates the exact size in bytes of the QOI image [`QoiEncoder::process_pixels_planar`] would produce.
+    ///
+    /// The size is calculated the same way as for [`QoiEncoder::encoded_size`].
+    #[must_use]
+    pub const fn encoded_size_planar<const P: usize>(&self, planes: [&[u8]; P]) -> usize {
+        self.size(PixelInput::Planar(&planes))
+    }
+    const fn size(&self, input: PixelInput) -> usize {
+        let mut state = self.state;
+        let mut size = 14 + 8;
+        while state.pixel_amount != 0 {
+            (state, _) = state.process_pixels::<64, false>(input, [0; 64]);
+            size += 64 - state.output_buffer_space;
+        }
+        size
+    }
     #[inline]
     const fn all_pixels_processed(&self) -> bool {
         self.state.pixel_amount == 0
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct QoiEncoderInternal {
     byte_index: usize,             // keeps track of input index, always increments
     seen_pixels: [Pixel; 64],
@@ -104,10 +596,26 @@ pub struct QoiEncoderInternal {
     pixel_amount: u64,             // keeps track of pixels to process, always decrements
     alpha: bool,                   // determines whether input is 3 or 4 byte pixels
     output_buffer_space: usize,    // how much of the output buffer is free space
+    stats: QoiStats,               // only updated when collecting statistics
+    reference_parity: bool,        // whether run chunks leave seen pixels untouched like the reference encoder
+    transparent_pixels: QoiTransparentPixels,
+    last_transparent: Option<Pixel>, // last transparent pixel read from input after any change
+    last_changed: bool,            // whether the last pixel read from input was changed
+    premultiply: Option<QoiPremultiply>,
+    colorspace: u8,                // decides whether premultiplied alpha is removed in linear light
+    width: u32,                    // used to find the position of a pixel for dithering
+    reduction: QoiReduction,
+    color_keys: Option<ColorKeys>, // colours read as fully transparent pixels
+    total_pixels: u64,             // used with indexed input which has no values to count
+    index_bits: u8,                // bits per palette index of indexed input
+    index_stride: usize,           // bytes per row of indexed input
+    yuv: (QoiYuvLayout, QoiYuvMatrix), // layout and matrix of YCbCr input
+    plane_strides: [usize; 4],     // bytes per row of every plane of planar input
+    run: u8,                       // length of the run chunk in progress when pushing one pixel at a time
 }
 
 impl QoiEncoderInternal {
-    const fn new(pixel_amount: u64, alpha: bool) -> Self {
+    pub(crate) const fn new(pixel_amount: u64, alpha: bool, colorspace: u8, width: u32) -> Self {
         Self {
             byte_index: 0,
             seen_pixels: [ZERO_PIXEL; 64],
@@ -115,63 +623,232 @@ impl QoiEncoderInternal {
             pixel_amount,
             alpha,
             output_buffer_space: 0,
+            stats: QoiStats::new(if alpha {4} else {3}),
+            reference_parity: false,
+            transparent_pixels: QoiTransparentPixels::Keep,
+            last_transparent: None,
+            last_changed: false,
+            premultiply: None,
+            colorspace,
+            width,
+            reduction: QoiReduction::Round,
+            color_keys: None,
+            total_pixels: pixel_amount,
+            index_bits: 8,
+            index_stride: 0,
+            yuv: (QoiYuvLayout::I420, QoiYuvMatrix::Bt601),
+            plane_strides: [0; 4],
+            run: 0,
         }
     }
-    #[allow(clippy::cast_possible_truncation)] // index guaranteed to be 0..=63 so cannot truncate when casting to u8
     #[inline]
-    const fn process_pixels<const N: usize>(mut self, input: &[u8], mut output: [u8; N]) -> (Self, [u8; N]) {
+    const fn process_pixels<const N: usize, const STATS: bool>(mut self,
+                                                               input: PixelInput,
+                                                               mut output: [u8; N]) -> (Self, [u8; N]) {
         let mut output_index = 0;
         while self.pixel_amount != 0 {
             let new_pixel; (self, new_pixel) = self.advance_input_pixel(input);
-            let index = new_pixel.calculate_hash_index();
-            let index_pixel = self.seen_pixels[index];
-            if index_pixel.is_same(new_pixel) {
-                if self.previous_pixel.is_same(new_pixel) {
-                    (self, output, output_index) = self.run_chunk(input, output, output_index);
-                } else {
-                    output[output_index] = index as u8; // QOI_OP_INDEX: 2bit tag (00), 6bit val (000000)
+            let changed = self.last_changed; // run chunks read further pixels
+            match self.chunk(new_pixel) {
+                None => (self, output, output_index) = self.run_chunk::<N, STATS>(input, output, output_index),
+                Some(QoiOp::Index(index)) => {
+                    output[output_index] = index; // QOI_OP_INDEX: 2bit tag (00), 6bit val (000000)
                     output_index += 1;
-                }
-            } else if new_pixel.alpha == self.previous_pixel.alpha {
-                if (self.byte_index == 3 || self.byte_index == 4) && self.previous_pixel.is_same(new_pixel) {
-                    (self, output, output_index) = self.run_chunk(input, output, output_index);
-                } else if let Some(diff) = new_pixel.diff(self.previous_pixel) {
-                    output[output_index] = diff; // QOI_OP_DIFF: 2bit tag (01), 3x2bit rgb diff (00)
-                    output_index += 1;
-                } else if let Some((tag_green, red_blue)) = new_pixel.luma(self.previous_pixel) {
-                    if output.len() - output_index < 2 {self = self.rewind_input_index(); break;}
-                    output[output_index] = tag_green; // QOI_OP_LUMA: 2bit tag (10), 6bit green diff
-                    output_index += 1;
-                    output[output_index] = red_blue; // 4bit red diff, 4bit blue diff (both based on green diff)
-                    output_index += 1;
-                } else { // must be RGB chunk
+                    if STATS {self.stats = self.stats.record(INDEX, 1);}
+                },
+                Some(QoiOp::Rgb(..)) => {
                     if output.len() - output_index < 4 {self = self.rewind_input_index(); break;}
                     (output, output_index) = new_pixel.rgb_to_output(output, output_index);
-                }
-            } else { // must be RGBA chunk
-                if output.len() - output_index < 5 {self = self.rewind_input_index(); break;}
-                (output, output_index) = new_pixel.rgba_to_output(output, output_index);
+                    if STATS {self.stats = self.stats.record(RGB, 1);}
+                },
+                Some(QoiOp::Rgba(..)) => {
+                    if output.len() - output_index < 5 {self = self.rewind_input_index(); break;}
+                    (output, output_index) = new_pixel.rgba_to_output(output, output_index);
+                    if STATS {self.stats = self.stats.record(RGBA, 1);}
+                },
+                Some(op) => { // QOI_OP_DIFF or QOI_OP_LUMA, chosen chunks are always valid so only a full buffer fails
+                    let Ok(written) = op.write(output, output_index) else {self = self.rewind_input_index(); break;};
+                    (output, output_index) = written;
+                    if STATS {self.stats = self.stats.record(if op.size() == 1 {DIFF} else {LUMA}, 1);}
+                },
             }
-            self.previous_pixel = new_pixel;
-            self.seen_pixels[index] = new_pixel;
+            self = self.remember(new_pixel);
+            if STATS {self.stats = self.stats.record_alpha(new_pixel.alpha);} // pixels of a run chunk repeat this pixel
             self.pixel_amount -= 1;
+            if STATS && changed {self.stats = self.stats.record_changed();}
             if output_index == output.len() {break;}
         }
         self.output_buffer_space = output.len() - output_index;
         (self, output)
     }
+    // chooses the chunk for the pixel. returns None when the pixel repeats the previous pixel and continues a run chunk
+    #[allow(clippy::cast_possible_truncation)] // index guaranteed to be 0..=63 so cannot truncate when casting to u8
+    #[inline]
+    pub(crate) const fn chunk(&self, pixel: Pixel) -> Option<QoiOp> {
+        if self.previous_pixel.is_same(pixel) {return None;} // even when not seen, such as a first pixel of 0, 0, 0, 255
+        let index = pixel.calculate_hash_index();
+        if self.seen_pixels[index].is_same(pixel) {return Some(QoiOp::Index(index as u8));}
+        if pixel.alpha != self.previous_pixel.alpha {
+            return Some(QoiOp::Rgba(pixel.red, pixel.green, pixel.blue, pixel.alpha));
+        }
+        if let Some(diff) = pixel.diff(self.previous_pixel) {return Some(QoiOp::from_input(&[diff, 0, 0, 0, 0], 0));}
+        if let Some((tag_green, red_blue)) = pixel.luma(self.previous_pixel) {
+            return Some(QoiOp::from_input(&[tag_green, red_blue, 0, 0, 0], 0));
+        }
+        Some(QoiOp::Rgb(pixel.red, pixel.green, pixel.blue))
+    }
+    // remembers the pixel once its chunk is chosen. with reference parity run chunks leave the seen pixels untouched
+    #[inline]
+    const fn remember(mut self, pixel: Pixel) -> Self {
+        if !self.reference_parity || !self.previous_pixel.is_same(pixel) {
+            self.seen_pixels[pixel.calculate_hash_index()] = pixel;
+        }
+        self.previous_pixel = pixel;
+        self
+    }
+    // encodes a single pixel for callers without a slice of input, such as the linter and the re-encoder.
+    // returns the run chunk finished by the pixel followed by the chunk of the pixel
+    pub(crate) const fn push_pixel(mut self, pixel: Pixel) -> (Self, Option<QoiOp>, Option<QoiOp>) {
+        let op = self.chunk(pixel);
+        self = self.remember(pixel);
+        if op.is_none() {
+            self.run += 1;
+            if self.run < 62 {return (self, None, None);} // a run chunk holds at most 62 pixels
+        }
+        let run = if self.run == 0 {None} else {Some(QoiOp::Run(self.run))};
+        self.run = 0;
+        (self, run, op)
+    }
+    // returns the run chunk in progress. must be called after pushing the last pixel
+    pub(crate) const fn finish_pixels(mut self) -> (Self, Option<QoiOp>) {
+        let run = if self.run == 0 {None} else {Some(QoiOp::Run(self.run))};
+        self.run = 0;
+        (self, run)
+    }
+    #[inline]
+    const fn is_byte_index_safe(&self, input: PixelInput) -> bool {
+        match input {
+            PixelInput::Bytes(input) => self.byte_index < input.len(),
+            PixelInput::LinearU16(input) | PixelInput::U16(input) => self.byte_index < input.len(),
+            PixelInput::LinearF32(input) | PixelInput::F32(input) => self.byte_index < input.len(),
+            PixelInput::Indexed(..) | PixelInput::Yuv(..) | PixelInput::Planar(..) => {
+                ((self.byte_index / self.channels()) as u64) < self.total_pixels
+            },
+        }
+    }
+    #[inline]
+    const fn advance_input_pixel(mut self, input: PixelInput) -> (Self, Pixel) {
+        let red = self.input_value(input, true); self.byte_index += 1;
+        let green = self.input_value(input, true); self.byte_index += 1;
+        let blue = self.input_value(input, true); self.byte_index += 1;
+        let mut alpha = 255;
+        if self.alpha {alpha = self.input_value(input, false); self.byte_index += 1;}
+        let mut pixel = Pixel::new(red, green, blue, alpha);
+        if let Some(mode) = self.premultiply {pixel = unpremultiply(pixel, mode.is_linear_light(self.colorspace));}
+        let keyed = match self.color_keys {
+            Some(color_keys) => pixel.alpha != 0 && color_keys.matches(pixel),
+            None => false,
+        };
+        if keyed {pixel.alpha = 0;}
+        if pixel.alpha != 0 {self.last_changed = false; return (self, pixel);}
+        let new_pixel = match (self.transparent_pixels, self.last_transparent) {
+            (QoiTransparentPixels::Keep, _) | (QoiTransparentPixels::Previous, None) => pixel,
+            (QoiTransparentPixels::Zero, _) => ZERO_PIXEL,
+            (QoiTransparentPixels::Previous, Some(last_transparent)) => last_transparent,
+        };
+        self.last_transparent = Some(new_pixel);
+        self.last_changed = keyed || !new_pixel.is_same(pixel);
+        (self, new_pixel)
+    }
+    // reads the input value at the input index as a byte
     #[inline]
-    const fn is_byte_index_safe(&self, input: &[u8]) -> bool {
-        self.byte_index < input.len()
+    const fn input_value(&self, input: PixelInput, colour: bool) -> u8 {
+        let srgb = colour && self.colorspace == 0;
+        match input {
+            PixelInput::Bytes(input) => input[self.byte_index],
+            PixelInput::LinearU16(input) => {
+                if srgb {linear_to_srgb(input[self.byte_index])} else {u16_to_u8(input[self.byte_index])}
+            },
+            PixelInput::LinearF32(input) => {
+                if srgb {linear_f32_to_srgb(input[self.byte_index])} else {f32_to_u8(input[self.byte_index])}
+            },
+            PixelInput::U16(input) => {
+                let (x, y) = self.position();
+                reduce_u16(input[self.byte_index], self.reduction, x, y)
+            },
+            PixelInput::F32(input) => {
+                let (x, y) = self.position();
+                reduce_f32(input[self.byte_index], self.reduction, x, y)
+            },
+            PixelInput::Indexed(indices, palette) => {
+                let channels = self.channels();
+                let index = self.palette_index(indices, (self.byte_index / channels) as u64);
+                palette[index as usize][self.byte_index % channels]
+            },
+            PixelInput::Yuv(frame) => {
+                let pixel = self.yuv_pixel(frame, (self.byte_index / 3) as u64);
+                match self.byte_index % 3 {
+                    0 => pixel.red,
+                    1 => pixel.green,
+                    _ => pixel.blue,
+                }
+            },
+            PixelInput::Planar(planes) => {
+                let channels = self.channels();
+                let plane = self.byte_index % channels;
+                let (position, width) = (self.byte_index / channels, self.width as usize);
+                let (x, y) = (position % width, position / width);
+                planes[plane][y * self.plane_strides[plane] + x]
+            },
+        }
+    }
+    // converts the luma and chroma values of the pixel at the position of a YCbCr frame
+    #[allow(clippy::cast_possible_truncation)] // positions within the frame always fit in a usize
+    #[inline]
+    const fn yuv_pixel(&self, frame: &[u8], position: u64) -> Pixel {
+        let (width, height) = (self.width as u64, self.total_pixels / self.width as u64);
+        let (x, y) = ((position % width) as usize, (position / width) as usize);
+        let (luma_plane, chroma_width, chroma_height) = ((width * height) as usize,
+                                                         width.div_ceil(2) as usize,
+                                                         height.div_ceil(2) as usize);
+        let (layout, matrix) = self.yuv;
+        let (luma, u, v) = match layout {
+            QoiYuvLayout::I420 => {
+                let chroma = luma_plane + y / 2 * chroma_width + x / 2;
+                (frame[y * width as usize + x], frame[chroma], frame[chroma + chroma_width * chroma_height])
+            },
+            QoiYuvLayout::Nv12 => {
+                let chroma = luma_plane + y / 2 * chroma_width * 2 + x / 2 * 2;
+                (frame[y * width as usize + x], frame[chroma], frame[chroma + 1])
+            },
+            QoiYuvLayout::Yuyv => {
+                let base = y * chroma_width * 4 + x / 2 * 4;
+                (frame[base + x % 2 * 2], frame[base + 1], frame[base + 3])
+            },
+        };
+        yuv_to_rgb(luma, u, v, matrix)
+    }
+    // reads the palette index of the pixel at the position from rows of packed indices
+    #[allow(clippy::cast_possible_truncation)] // position within a row or column always fits in a u32
+    #[inline]
+    const fn palette_index(&self, indices: &[u8], position: u64) -> u8 {
+        let (x, y) = ((position % self.width as u64) as usize, (position / self.width as u64) as usize);
+        let bit = x * self.index_bits as usize;
+        let byte = indices[y * self.index_stride + bit / 8];
+        let mask = ((1u16 << self.index_bits) - 1) as u8;
+        (byte >> (8 - self.index_bits as usize - bit % 8)) & mask
     }
     #[inline]
-    const fn advance_input_pixel(mut self, input: &[u8]) -> (Self, Pixel) {
-        let red = input[self.byte_index]; self.byte_index += 1;
-        let green = input[self.byte_index]; self.byte_index += 1;
-        let blue = input[self.byte_index]; self.byte_index += 1;
-        let mut alpha = self.previous_pixel.alpha;
-        if self.alpha {alpha = input[self.byte_index]; self.byte_index += 1;}
-        (self, Pixel::new(red, green, blue, alpha))
+    const fn channels(&self) -> usize {
+        if self.alpha {4} else {3}
+    }
+    // position of the pixel at the input index, only calculated when dithering
+    #[allow(clippy::cast_possible_truncation)] // only the remainder of the position is used so truncation is harmless
+    #[inline]
+    const fn position(&self) -> (u32, u32) {
+        if !matches!(self.reduction, QoiReduction::OrderedDither) {return (0, 0);}
+        let pixel = (self.byte_index / if self.alpha {4} else {3}) as u64;
+        ((pixel % self.width as u64) as u32, (pixel / self.width as u64) as u32)
     }
     #[inline]
     const fn rewind_input_index(mut self) -> Self {
@@ -179,30 +856,51 @@ impl QoiEncoderInternal {
         self
     }
     #[inline]
-    const fn run_chunk<const N: usize>(mut self,
-                                       input: &[u8],
-                                       mut output: [u8; N],
-                                       mut output_index: usize) -> (Self, [u8; N], usize) {
+    const fn run_chunk<const N: usize, const STATS: bool>(mut self,
+                                                          input: PixelInput,
+                                                          mut output: [u8; N],
+                                                          mut output_index: usize) -> (Self, [u8; N], usize) {
         let mut new_pixel; let mut run = 0; // QOI_OP_RUN: 2bit tag (11), 6bit val (000000), bias -1 (0 means a run of 1)
         while self.is_byte_index_safe(input) {
             (self, new_pixel) = self.advance_input_pixel(input);
             if self.previous_pixel.is_same(new_pixel) && run < 61 { // bias -1 (61 means a run of 62)
                 run += 1;
                 self.pixel_amount -= 1;
+                if STATS && self.last_changed {self.stats = self.stats.record_changed();}
             } else {
                 self = self.rewind_input_index(); break;
             }
         }
+        if STATS {self.stats = self.stats.record(RUN, run as u64 + 1);}
         run |= 0xc0; // apply bitwise OR to add tag
         output[output_index] = run; output_index += 1;
         (self, output, output_index)
     }
 }
 
+// the pixel data given to the encoder. every variant holds or represents 3 or 4 values per pixel
+#[derive(Clone, Copy)]
+pub enum PixelInput<'a> {
+    Bytes(&'a [u8]),
+    LinearU16(&'a [u16]), // linear light values converted to bytes based on the colorspace
+    LinearF32(&'a [f32]), // linear light values converted to bytes based on the colorspace
+    U16(&'a [u16]),       // values reduced to bytes as chosen
+    F32(&'a [f32]),       // values reduced to bytes as chosen
+    Indexed(&'a [u8], &'a [[u8; 4]]), // indices looked up in the palette, counted as 3 or 4 values per pixel
+    Yuv(&'a [u8]),        // luma and chroma values converted to RGB, counted as 3 values per pixel
+    Planar(&'a [&'a [u8]]), // a value of every plane for each pixel
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{error::QoiError, utils::is_identical};
-    use super::{QoiEncoder, QoiEncoderProgress};
+    use crate::{
+        color::{QoiPremultiply, QoiReduction, QoiYuvLayout, QoiYuvMatrix},
+        error::QoiError,
+        op::QoiOp,
+        pixel::Pixel,
+        utils::is_identical,
+    };
+    use super::{QoiEncoder, QoiEncoderInternal, QoiEncoderProgress, QoiTransparentPixels};
     #[test]
     const fn good_new_four_byte() {
         let input = [255, 255, 255, 255,
@@ -370,7 +1068,7 @@ mod tests {
             assert!(progress.is_ok());
             if let Ok(progress) = progress {
                 match progress {
-                    QoiEncoderProgress::Finished(buffer, empty) => {
+                    QoiEncoderProgress::Finished(buffer, empty, _) => {
                         assert!(is_identical(
                             &buffer, &[0,                       // [0, 0, 0, 0] encoded as index chunk
                                        127,                     // [1, 1, 1, 0] encoded as diff chunk
@@ -468,6 +1166,622 @@ mod tests {
         }
     }
     #[test]
+    const fn infallible_encoded_size() {
+        let input = [0, 0, 0, 255,        // run chunk   (1 byte)
+                     0, 0, 0, 222,        // rgba chunk  (5 bytes)
+                     0, 0, 0, 222,        // run chunk   (1 byte)
+                     0, 0, 0, 222,        // (part of previous run chunk)
+                     0, 0, 0, 255,        // index chunk (1 byte)
+                     0, 0, 0, 222,        // index chunk (1 byte)
+                     0, 0, 0, 222,        // run chunk   (1 byte)
+                     0, 2, 0, 222,        // luma chunk  (2 bytes)
+                     128, 128, 128, 222,  // rgb chunk   (4 bytes)
+                     255, 255, 255, 255,  // rgba chunk  (5 bytes)
+                     255, 255, 255, 255,  // run chunk   (1 byte)
+                     255, 255, 255, 255]; // (part of previous run chunk)
+        let both = QoiEncoder::new(&input, 2, 6, 4, 0);
+        assert!(both.is_ok());
+        if let Ok((encoder, _)) = both {
+            assert!(encoder.encoded_size(&input) == 14 + 22 + 8);
+            let progress = encoder.process_pixels(&input, [0; 20]);
+            assert!(progress.is_ok());
+            if let Ok(QoiEncoderProgress::Unfinished(encoder, _, empty)) = progress {
+                assert!(empty == 4);
+                assert!(encoder.encoded_size(&input) == 14 + 6 + 8); // only remaining pixels count
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_pixels_stats() {
+        let input = [0, 0, 0, 255,        // run chunk   (run of 1)
+                     0, 0, 0, 222,        // rgba chunk
+                     0, 0, 0, 222,        // run chunk   (run of 2)
+                     0, 0, 0, 222,
+                     0, 0, 0, 255,        // index chunk
+                     0, 0, 0, 222,        // index chunk
+                     0, 0, 0, 222,        // run chunk   (run of 1)
+                     0, 2, 0, 222,        // luma chunk
+                     128, 128, 128, 222,  // rgb chunk
+                     255, 255, 255, 255,  // rgba chunk
+                     255, 255, 255, 255,  // run chunk   (run of 2)
+                     255, 255, 255, 255];
+        let both = QoiEncoder::new(&input, 2, 6, 4, 0);
+        assert!(both.is_ok());
+        if let Ok((encoder, _)) = both {
+            let progress = encoder.with_stats().process_pixels(&input, [0; 64]);
+            assert!(progress.is_ok());
+            if let Ok(QoiEncoderProgress::Finished(_, empty, stats)) = progress {
+                assert!(empty == 42);
+                assert!(stats.rgb_chunks() == 1);
+                assert!(stats.rgba_chunks() == 2);
+                assert!(stats.index_chunks() == 2);
+                assert!(stats.diff_chunks() == 0);
+                assert!(stats.luma_chunks() == 1);
+                assert!(stats.run_chunks() == 4);
+                assert!(stats.total_chunks() == 10);
+                assert!(stats.total_chunk_bytes() == 22);
+                assert!(stats.pixel_amount() == 12);
+                assert!(stats.longest_run() == 2);
+                assert!(matches!(stats.index_hit_rate(), (2, 6)));
+                assert!(matches!(stats.compression_ratio(), (44, 48))); // 22 chunk bytes + 22, 12 pixels of 4 bytes each
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    // chunks generated by the encoder of the reference qoi.h from the pixels of corpus_image
+    // reference qoi.h chunks for an 8x8 RGBA image from seed 1 with 0 leading 0, 0, 0, 255 pixels
+    const CORPUS_1: [u8; 121] = [
+        255, 0, 0, 0, 28, 255, 0, 0, 0, 103, 192, 141, 38, 169, 199, 191, 164, 79, 159, 228,
+        55, 255, 21, 21, 14, 255, 254, 87, 85, 21, 254, 0, 0, 0, 60, 63, 192, 53, 194, 255,
+        0, 0, 0, 61, 255, 0, 0, 0, 149, 110, 53, 193, 255, 0, 0, 0, 86, 53, 192, 87,
+        192, 53, 192, 93, 255, 255, 1, 255, 101, 162, 7, 143, 86, 107, 254, 176, 108, 91, 193, 48,
+        0, 254, 170, 234, 250, 53, 192, 255, 0, 0, 0, 170, 254, 204, 243, 188, 139, 165, 123, 66,
+        194, 156, 7, 114, 255, 173, 214, 160, 183, 255, 173, 214, 160, 88, 53, 196, 255, 0, 0, 0,
+        243];
+    // reference qoi.h chunks for an 8x8 RGB image from seed 2 with 0 leading 0, 0, 0, 255 pixels
+    const CORPUS_2: [u8; 93] = [
+        194, 108, 254, 157, 39, 201, 254, 0, 0, 0, 194, 162, 247, 254, 178, 44, 203, 130, 215, 53,
+        131, 193, 172, 69, 53, 178, 34, 193, 53, 7, 254, 220, 55, 13, 193, 254, 109, 219, 182, 192,
+        80, 254, 128, 96, 24, 53, 109, 254, 142, 99, 216, 53, 192, 34, 150, 21, 61, 182, 5, 133,
+        69, 79, 66, 196, 164, 208, 192, 26, 254, 45, 11, 2, 53, 254, 39, 9, 2, 45, 192, 53,
+        132, 229, 53, 194, 128, 231, 53, 192, 254, 240, 60, 15, 192];
+    // reference qoi.h chunks for a 10x10 RGBA image from seed 3 with 70 leading 0, 0, 0, 255 pixels
+    const CORPUS_3: [u8; 70] = [
+        253, 199, 170, 38, 184, 208, 254, 15, 67, 80, 192, 255, 15, 67, 80, 8, 255, 0, 0, 0,
+        255, 254, 9, 130, 32, 53, 193, 254, 13, 3, 128, 255, 13, 3, 128, 36, 254, 199, 241, 124,
+        192, 254, 156, 167, 169, 192, 254, 13, 3, 128, 254, 156, 167, 169, 183, 113, 192, 188, 208, 192,
+        2, 193, 127, 2, 192, 254, 114, 92, 87, 17];
+    // reference qoi.h chunks for a 16x4 RGBA image from seed 4 with 1 leading 0, 0, 0, 255 pixels
+    const CORPUS_4: [u8; 135] = [
+        193, 255, 0, 0, 0, 230, 159, 69, 255, 0, 0, 0, 255, 193, 67, 254, 146, 100, 25, 74,
+        169, 230, 254, 104, 218, 54, 192, 254, 115, 92, 151, 53, 192, 254, 92, 23, 197, 254, 150, 101,
+        25, 178, 180, 194, 91, 190, 129, 192, 126, 168, 182, 53, 254, 171, 234, 122, 81, 53, 192, 156,
+        132, 255, 252, 252, 248, 189, 192, 190, 33, 53, 174, 4, 255, 6, 14, 10, 215, 7, 255, 252,
+        252, 248, 245, 91, 193, 255, 251, 252, 249, 4, 162, 178, 255, 0, 254, 245, 192, 192, 255, 0,
+        254, 245, 128, 97, 90, 254, 216, 118, 29, 255, 251, 252, 249, 4, 21, 53, 254, 198, 177, 236,
+        123, 193, 255, 199, 177, 237, 83, 53, 255, 0, 0, 0, 217, 53, 96];
+    // reference qoi.h chunks for a 5x16 RGB image from seed 5 with 64 leading 0, 0, 0, 255 pixels
+    const CORPUS_5: [u8; 30] = [
+        253, 196, 254, 144, 228, 249, 192, 254, 0, 0, 0, 192, 142, 230, 53, 192, 254, 195, 176, 236,
+        53, 254, 31, 199, 177, 254, 98, 152, 166, 193];
+    type CorpusImage = (u32, u32, u8, u32, usize, &'static [u8]); // width, height, channels, seed, lead, chunks
+    // generates a mix of pixels that exercises every chunk. the sequence comes from a linear congruential generator
+    const fn corpus_image(pixels: usize, channels: usize, seed: u32, lead: usize) -> [u8; 512] {
+        let mut output = [0; 512];
+        let mut state = seed;
+        let mut previous: [u8; 4] = [0, 0, 0, 255];
+        let mut pixel_index = 0;
+        while pixel_index < pixels {
+            let mut pixel = previous;
+            if pixel_index < lead {
+                pixel = [0, 0, 0, 255];
+            } else {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let random = state >> 16;
+                match random % 8 {
+                    0 | 1 => {}, // same as previous pixel
+                    2 => { // small difference
+                        let mut channel = 0;
+                        while channel < 3 {
+                            let diff = ((random >> (3 + channel * 2)) % 4) as u8;
+                            pixel[channel as usize] = previous[channel as usize].wrapping_add(diff).wrapping_sub(2);
+                            channel += 1;
+                        }
+                    },
+                    3 => { // difference based on green
+                        let green = (((random >> 3) % 64) as u8).wrapping_sub(32);
+                        pixel[0] = previous[0].wrapping_add(green).wrapping_add(((random >> 9) % 16) as u8).wrapping_sub(8);
+                        pixel[1] = previous[1].wrapping_add(green);
+                        pixel[2] = previous[2].wrapping_add(green).wrapping_add(((random >> 13) % 16) as u8).wrapping_sub(8);
+                    },
+                    4 => { // recently seen pixel
+                        let back = 1 + (random as usize >> 3) % 8;
+                        if pixel_index >= back {
+                            let mut channel = 0;
+                            while channel < channels {
+                                pixel[channel] = output[(pixel_index - back) * channels + channel];
+                                channel += 1;
+                            }
+                        }
+                    },
+                    5 => pixel = [(random >> 3) as u8, (random >> 5) as u8, (random >> 7) as u8, previous[3]],
+                    6 => if channels == 4 {pixel[3] = (random >> 4) as u8;},
+                    _ => pixel = [0, 0, 0, 255],
+                }
+            }
+            if channels == 3 {pixel[3] = 255;}
+            let mut channel = 0;
+            while channel < channels {
+                output[pixel_index * channels + channel] = pixel[channel];
+                channel += 1;
+            }
+            previous = pixel;
+            pixel_index += 1;
+        }
+        output
+    }
+    #[allow(clippy::cast_possible_truncation)] // corpus images are small
+    #[test]
+    const fn good_reference_parity() {
+        let corpus: [CorpusImage; 5] = [(8, 8, 4, 1, 0, &CORPUS_1),
+                                        (8, 8, 3, 2, 0, &CORPUS_2),
+                                        (10, 10, 4, 3, 70, &CORPUS_3),
+                                        (16, 4, 4, 4, 1, &CORPUS_4),
+                                        (5, 16, 3, 5, 64, &CORPUS_5)];
+        let mut image = 0;
+        while image < corpus.len() {
+            let (width, height, channels, seed, lead, expected) = corpus[image];
+            let length = width as usize * height as usize * channels as usize;
+            let input = corpus_image(width as usize * height as usize, channels as usize, seed, lead);
+            let input = input.split_at(length).0;
+            let both = QoiEncoder::new(input, width, height, channels, 0);
+            assert!(both.is_ok());
+            if let Ok((encoder, _)) = both {
+                let encoder = encoder.with_reference_parity();
+                assert!(encoder.encoded_size(input) == 14 + expected.len() + 8);
+                let progress = encoder.process_pixels(input, [0; 256]);
+                if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = progress {
+                    assert!(is_identical(buffer.split_at(256 - empty).0, expected));
+                } else {
+                    unreachable!();
+                }
+            }
+            image += 1;
+        }
+        let input = corpus_image(64, 3, 2, 0);
+        let input = input.split_at(192).0;
+        if let Ok((encoder, _)) = QoiEncoder::new(input, 8, 8, 3, 0) { // first pixel run later encoded as index chunk
+            assert!(encoder.encoded_size(input) == 14 + CORPUS_2.len() - 3 + 8);
+        }
+    }
+    #[test]
+    const fn good_process_pixels_transparent() {
+        let input = [10, 20, 30, 0,       // transparent
+                     40, 50, 60, 0,       // transparent
+                     0, 0, 0, 0,          // transparent
+                     200, 100, 50, 255,
+                     1, 2, 3, 0,          // transparent
+                     200, 100, 50, 255,
+                     9, 9, 9, 0,          // transparent
+                     9, 9, 9, 0];         // transparent
+        let both = QoiEncoder::new(&input, 2, 4, 4, 0);
+        assert!(both.is_ok());
+        if let Ok((encoder, _)) = both {
+            let encoder = encoder.with_transparent_pixels(QoiTransparentPixels::Zero).with_stats();
+            let progress = encoder.process_pixels(&input, [0; 16]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, stats)) = progress {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[0,                       // [0, 0, 0, 0] encoded as index chunk
+                                       193,                     // [0, 0, 0, 0] encoded as run chunk (run of 2)
+                                       255, 200, 100, 50, 255,  // [200, 100, 50, 255] encoded as rgba chunk
+                                       0,                       // [0, 0, 0, 0] encoded as index chunk
+                                       31,                      // [200, 100, 50, 255] encoded as index chunk
+                                       0,                       // [0, 0, 0, 0] encoded as index chunk
+                                       192]));                  // [0, 0, 0, 0] encoded as run chunk (run of 1)
+                assert!(stats.changed_pixels() == 5);
+            } else {
+                unreachable!();
+            }
+        }
+        let both = QoiEncoder::new(&input, 2, 4, 4, 0);
+        if let Ok((encoder, _)) = both {
+            let encoder = encoder.with_transparent_pixels(QoiTransparentPixels::Previous).with_stats();
+            let progress = encoder.process_pixels(&input, [0; 16]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, stats)) = progress {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[255, 10, 20, 30, 0,      // [10, 20, 30, 0] encoded as rgba chunk
+                                       193,                     // [10, 20, 30, 0] encoded as run chunk (run of 2)
+                                       255, 200, 100, 50, 255,  // [200, 100, 50, 255] encoded as rgba chunk
+                                       20,                      // [10, 20, 30, 0] encoded as index chunk
+                                       31,                      // [200, 100, 50, 255] encoded as index chunk
+                                       20,                      // [10, 20, 30, 0] encoded as index chunk
+                                       192]));                  // [10, 20, 30, 0] encoded as run chunk (run of 1)
+                assert!(stats.changed_pixels() == 5);
+            } else {
+                unreachable!();
+            }
+        }
+        let both = QoiEncoder::new(&input, 2, 4, 4, 0);
+        if let Ok((encoder, _)) = both {
+            let encoder = encoder.with_stats();
+            if let Ok(QoiEncoderProgress::Finished(_, empty, stats)) = encoder.process_pixels(&input, [0; 64]) {
+                assert!(empty == 64 - 25); // 4 rgba chunks, 1 luma chunk, 2 index chunks and 1 run chunk
+                assert!(stats.changed_pixels() == 0);
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_pixels_premultiplied() {
+        let input = [128, 64, 0, 128,         // premultiplied
+                     10, 20, 30, 0,           // premultiplied and fully transparent
+                     200, 100, 50, 255];      // opaque so unchanged
+        if let Ok((encoder, _)) = QoiEncoder::new(&input, 3, 1, 4, 1) {
+            let progress = encoder.with_premultiplied_alpha(QoiPremultiply::LinearLight).process_pixels(&input, [0; 16]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = progress {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[255, 255, 128, 0, 128,   // [255, 128, 0, 128] encoded as rgba chunk
+                                       0,                       // [0, 0, 0, 0] encoded as index chunk
+                                       255, 200, 100, 50, 255])); // [200, 100, 50, 255] encoded as rgba chunk
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new(&input, 3, 1, 4, 0) {
+            let progress = encoder.with_premultiplied_alpha(QoiPremultiply::LinearLight).process_pixels(&input, [0; 5]);
+            if let Ok(QoiEncoderProgress::Unfinished(_, buffer, _)) = progress {
+                assert!(is_identical(&buffer, &[255, 175, 90, 0, 128])); // divided in linear light
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_pixels_linear() {
+        let input = [14146, 0, 65535, 32896,  // linear light values
+                     20, 40, 60, 32896,
+                     20, 40, 60, 32896];
+        if let Ok((encoder, _)) = QoiEncoder::new_u16(&input, 3, 1, 4, 0) {
+            assert!(encoder.encoded_size_linear_u16(&input) == 14 + 10 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_linear_u16(&input, [0; 16]) {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[255, 128, 0, 255, 128,  // [128, 0, 255, 128] encoded as rgba chunk
+                                       254, 1, 2, 3,           // [1, 2, 3, 128] encoded as rgb chunk
+                                       192]));                 // [1, 2, 3, 128] encoded as run chunk
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new_u16(&input, 3, 1, 4, 1) {
+            if let Ok(QoiEncoderProgress::Unfinished(_, buffer, _)) = encoder.process_pixels_linear_u16(&input, [0; 5]) {
+                assert!(is_identical(&buffer, &[255, 55, 0, 255, 128])); // rounded without conversion
+            } else {
+                unreachable!();
+            }
+        }
+        let input = [0.5, -1.0, 2.0,          // linear light values, out of range values are clamped
+                     f32::NAN, 0.0, 0.0];
+        if let Ok((encoder, _)) = QoiEncoder::new_f32(&input, 1, 2, 3, 0) {
+            assert!(encoder.encoded_size_linear_f32(&input) == 14 + 8 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_linear_f32(&input, [0; 16]) {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[254, 188, 0, 255,       // [188, 0, 255] encoded as rgb chunk
+                                       254, 0, 0, 0]));        // [0, 0, 0] encoded as rgb chunk
+            } else {
+                unreachable!();
+            }
+        }
+        assert!(matches!(QoiEncoder::new_f32(&input, 2, 2, 3, 0), Err(QoiError::InputHeaderMismatch(2, 2, 1))));
+    }
+    #[test]
+    const fn good_process_pixels_reduction() {
+        let input = [65407, 65407, 65407,     // 254.5 after scaling
+                     65407, 65407, 65407,
+                     65407, 65407, 65407,
+                     65407, 65407, 65407];
+        if let Ok((encoder, _)) = QoiEncoder::new_u16(&input, 4, 1, 3, 0) {
+            assert!(encoder.encoded_size_u16(&input) == 14 + 2 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_u16(&input, [0; 8]) {
+                assert!(is_identical(buffer.split_at(8 - empty).0,
+                                     &[85,                     // [255, 255, 255] encoded as diff chunk (rounded)
+                                       194]));                 // [255, 255, 255] encoded as run chunk (run of 3)
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new_u16(&input, 4, 1, 3, 0) {
+            let progress = encoder.with_reduction(QoiReduction::Truncate).process_pixels_u16(&input, [0; 8]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = progress {
+                assert!(is_identical(buffer.split_at(8 - empty).0,
+                                     &[64,                     // [254, 254, 254] encoded as diff chunk (truncated)
+                                       194]));                 // [254, 254, 254] encoded as run chunk (run of 3)
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new_u16(&input, 4, 1, 3, 0) {
+            let progress = encoder.with_reduction(QoiReduction::OrderedDither).process_pixels_u16(&input, [0; 8]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = progress {
+                assert!(is_identical(buffer.split_at(8 - empty).0,
+                                     &[64,                     // [254, 254, 254] encoded as diff chunk
+                                       127,                    // [255, 255, 255] encoded as diff chunk
+                                       23,                     // [254, 254, 254] encoded as index chunk
+                                       38]));                  // [255, 255, 255] encoded as index chunk
+            } else {
+                unreachable!();
+            }
+        }
+        let input = [0.5, 0.5, 0.5, 1.0,      // 127.5 after scaling
+                     0.5, 0.5, 0.5, 1.0,
+                     0.5, 0.5, 0.5, 1.0,
+                     0.5, 0.5, 0.5, 1.0];
+        if let Ok((encoder, _)) = QoiEncoder::new_f32(&input, 2, 2, 4, 0) {
+            let encoder = encoder.with_reduction(QoiReduction::OrderedDither);
+            assert!(encoder.encoded_size_f32(&input) == 14 + 7 + 8);
+            let progress = encoder.process_pixels_f32(&input, [0; 16]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = progress {
+                assert!(is_identical(buffer.split_at(16 - empty).0,
+                                     &[254, 127, 127, 127,     // [127, 127, 127, 255] encoded as rgb chunk
+                                       127,                    // [128, 128, 128, 255] encoded as diff chunk
+                                       192,                    // [128, 128, 128, 255] encoded as run chunk
+                                       38]));                  // [127, 127, 127, 255] encoded as index chunk
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_new_detect_channels() {
+        let opaque = [0, 0, 0, 255, 10, 10, 10, 255];
+        let transparent = [0, 0, 0, 255, 10, 10, 10, 254];
+        let rgb = [0, 0, 0, 10, 10, 10];
+        if let Ok((_, header)) = QoiEncoder::new_detect_channels(&opaque, 2, 1, 0) {
+            assert!(header.channels() == 3);
+        } else {
+            unreachable!();
+        }
+        if let Ok((_, header)) = QoiEncoder::new_detect_channels(&transparent, 1, 2, 0) {
+            assert!(header.channels() == 4);
+        } else {
+            unreachable!();
+        }
+        if let Ok((_, header)) = QoiEncoder::new_detect_channels(&rgb, 2, 1, 1) {
+            assert!(header.channels() == 3 && header.colorspace() == 1);
+        } else {
+            unreachable!();
+        }
+        assert!(matches!(QoiEncoder::new_detect_channels(&opaque, 3, 1, 0), Err(QoiError::IncorrectInputData(8, 3))));
+        assert!(matches!(QoiEncoder::new_detect_channels(&rgb, 3, 1, 0), Err(QoiError::InputHeaderMismatch(3, 1, 1))));
+        assert!(matches!(QoiEncoder::new_detect_channels(&rgb, 2, 1, 2), Err(QoiError::InvalidColorspaceValue(2))));
+    }
+    #[test]
+    const fn good_new_color_keyed() {
+        let input = [255, 0, 255, 10, 20, 30, 254, 1, 255]; // magenta, colour, almost magenta
+        if let Ok((encoder, header)) = QoiEncoder::new_color_keyed(&input, 3, 1, 0, &[[255, 0, 255]], 1) {
+            assert!(header.channels() == 4);
+            let encoder = encoder.with_stats();
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, stats)) = encoder.process_pixels(&input, [0; 16]) {
+                assert!(empty == 1);
+                assert!(is_identical(buffer.split_at(15).0, &[255, 255, 0, 255, 0,       // RGBA chunk (keyed)
+                                                               255, 10, 20, 30, 255,     // RGBA chunk
+                                                               255, 254, 1, 255, 0]));   // RGBA chunk (keyed)
+                assert!(stats.changed_pixels() == 2 && stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new_color_keyed(&input, 1, 3, 0, &[[0, 0, 0], [255, 0, 255]], 1) {
+            let encoder = encoder.with_transparent_pixels(QoiTransparentPixels::Zero).with_stats();
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, stats)) = encoder.process_pixels(&input, [0; 8]) {
+                assert!(empty == 1);
+                assert!(is_identical(buffer.split_at(7).0, &[0,                      // Index chunk (keyed and zeroed)
+                                                              255, 10, 20, 30, 255,   // RGBA chunk
+                                                              0]));                   // Index chunk (keyed and zeroed)
+                assert!(stats.changed_pixels() == 2 && stats.alpha_used()); // alpha only changed by index chunks
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        assert!(matches!(QoiEncoder::new_color_keyed(&input, 3, 1, 0, &[], 0), Err(QoiError::InvalidColorKeyAmount(0))));
+        assert!(matches!(QoiEncoder::new_color_keyed(&input, 3, 1, 0, &[[0; 3]; 9], 0),
+                         Err(QoiError::InvalidColorKeyAmount(9))));
+        assert!(matches!(QoiEncoder::new_color_keyed(&input, 4, 1, 0, &[[0; 3]], 0),
+                         Err(QoiError::InputHeaderMismatch(4, 1, 2))));
+    }
+    #[test]
+    const fn good_process_pixels_indexed() {
+        let palette = [[0, 0, 0, 255], [255, 0, 0, 255], [0, 255, 0, 255]];
+        let indices = [0b00_01_10_01, 255, // 2 bit indices 0, 1, 2, 1 and a padding byte
+                       0b01_01_01_01];     // 2 bit indices 1, 1, 1, 1
+        if let Ok((encoder, header)) = QoiEncoder::new_indexed(&indices, &palette, 2, 2, 4, 2, 0) {
+            assert!(header.channels() == 3);
+            assert!(encoder.encoded_size_indexed(&indices, &palette) == 14 + 5 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_indexed(&indices, &palette, [0; 8]) {
+                assert!(empty == 3);
+                assert!(is_identical(buffer.split_at(5).0, &[192, // Run chunk (amount 1)
+                                                             90,  // Diff chunk
+                                                             118, // Diff chunk
+                                                             50,  // Index chunk
+                                                             195, // Run chunk (amount 4)
+                                                            ]));
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        let palette = [[10, 20, 30, 255], [255, 0, 0, 128]];
+        if let Ok((encoder, header)) = QoiEncoder::new_indexed(&[1, 0], &palette, 8, 0, 2, 1, 0) {
+            assert!(header.channels() == 4);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_indexed(&[1, 0], &palette, [0; 10]) {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, &[255, 255, 0, 0, 128, 255, 10, 20, 30, 255])); // 2 RGBA chunks
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new_indexed(&[0b1010_0000], &palette, 1, 0, 3, 1, 0) { // fewer indices than bytes
+            assert!(encoder.encoded_size_indexed(&[0b1010_0000], &palette) == 14 + 11 + 8); // 2 RGBA chunks and an index chunk
+        } else {
+            unreachable!();
+        }
+        assert!(matches!(QoiEncoder::new_indexed(&indices, &palette, 2, 2, 4, 2, 0), Err(QoiError::PaletteIndexOutOfRange(2, 2))));
+        assert!(matches!(QoiEncoder::new_indexed(&indices, &palette, 3, 2, 4, 2, 0), Err(QoiError::InvalidIndexBits(3))));
+        assert!(matches!(QoiEncoder::new_indexed(&indices, &palette, 4, 1, 4, 2, 0), Err(QoiError::InvalidStride(1, 2))));
+        assert!(matches!(QoiEncoder::new_indexed(&indices, &palette, 2, 2, 4, 3, 0), Err(QoiError::InputHeaderMismatch(4, 3, 8))));
+        assert!(matches!(QoiEncoder::new_indexed(&indices, &[], 2, 2, 4, 2, 0), Err(QoiError::InvalidPaletteSize(0))));
+    }
+    #[test]
+    const fn good_process_pixels_yuv() {
+        let nv12 = [235, 235, 16, 16, // luma of 2 rows
+                    128, 128];        // Cb (U) and Cr (V)
+        if let Ok((encoder, header)) = QoiEncoder::new_yuv(&nv12, 2, 2, QoiYuvLayout::Nv12, QoiYuvMatrix::Bt709, 0) {
+            assert!(header.channels() == 3);
+            assert!(encoder.encoded_size_yuv(&nv12) == 14 + 4 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_yuv(&nv12, [0; 8]) {
+                assert!(empty == 4);
+                assert!(is_identical(buffer.split_at(4).0, &[85,    // Diff chunk (white)
+                                                             192,   // Run chunk (amount 1)
+                                                             127,   // Diff chunk (black)
+                                                             192]));// Run chunk (amount 1)
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        let i420 = [82, 235, 41,  // luma of a single row
+                    90, 128,      // Cb (U)
+                    240, 128];    // Cr (V)
+        if let Ok((encoder, _)) = QoiEncoder::new_yuv(&i420, 3, 1, QoiYuvLayout::I420, QoiYuvMatrix::Bt601, 0) {
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_yuv(&i420, [0; 12]) {
+                assert!(empty == 3);
+                assert!(is_identical(buffer.split_at(9).0, &[94,                 // Diff chunk (255, 1, 0)
+                                                             254, 255, 179, 178, // RGB chunk (shares the chroma of red)
+                                                             254, 29, 29, 29])); // RGB chunk
+            } else {
+                unreachable!();
+            }
+        }
+        let yuyv = [16, 128, 235, 128]; // black and white
+        if let Ok((encoder, _)) = QoiEncoder::new_yuv(&yuyv, 2, 1, QoiYuvLayout::Yuyv, QoiYuvMatrix::Bt601, 0) {
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_yuv(&yuyv, [0; 8]) {
+                assert!(empty == 6 && is_identical(buffer.split_at(2).0, &[192, 85])); // Run chunk and Diff chunk
+            } else {
+                unreachable!();
+            }
+        }
+        assert!(matches!(QoiEncoder::new_yuv(&yuyv, 2, 2, QoiYuvLayout::Yuyv, QoiYuvMatrix::Bt601, 0),
+                         Err(QoiError::IncorrectFrameSize(4, 8))));
+    }
+    #[test]
+    const fn good_process_pixels_planar() {
+        let red = [255, 255, 9, // white pixels followed by 1 byte of padding
+                   0, 0];       // black pixels, last row without padding
+        let green_blue = [255, 255, 0, 0];
+        let planes: [&[u8]; 3] = [&red, &green_blue, &green_blue];
+        if let Ok((encoder, header)) = QoiEncoder::new_planar(planes, [3, 0, 2], 2, 2, 0) {
+            assert!(header.channels() == 3);
+            assert!(encoder.encoded_size_planar(planes) == 14 + 4 + 8);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_planar(planes, [0; 8]) {
+                assert!(empty == 4);
+                assert!(is_identical(buffer.split_at(4).0, &[85,    // Diff chunk (white)
+                                                             192,   // Run chunk (amount 1)
+                                                             127,   // Diff chunk (black)
+                                                             192]));// Run chunk (amount 1)
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        let planes: [&[u8]; 4] = [&[1], &[2], &[3], &[4]];
+        if let Ok((encoder, header)) = QoiEncoder::new_planar(planes, [0; 4], 1, 1, 0) {
+            assert!(header.channels() == 4);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, _)) = encoder.process_pixels_planar(planes, [0; 8]) {
+                assert!(empty == 3 && is_identical(buffer.split_at(5).0, &[255, 1, 2, 3, 4])); // RGBA chunk
+            } else {
+                unreachable!();
+            }
+        }
+        let short: [&[u8]; 3] = [&red, &green_blue, &[0, 0, 0]];
+        assert!(matches!(QoiEncoder::new_planar(short, [3, 0, 0], 2, 2, 0), Err(QoiError::PlaneTooSmall(2, 3, 4))));
+        assert!(matches!(QoiEncoder::new_planar(short, [1, 0, 0], 2, 2, 0), Err(QoiError::InvalidStride(1, 2))));
+        assert!(matches!(QoiEncoder::new_planar([&red, &red], [0; 2], 2, 2, 0), Err(QoiError::InvalidPlaneAmount(2))));
+    }
+    #[test]
+    const fn good_process_pixels_alpha_used() {
+        let opaque = [0, 0, 0, 255, 10, 10, 10, 255];
+        let transparent = [0, 0, 0, 255, 10, 10, 10, 254, 0, 0, 0, 255];
+        if let Ok((encoder, _)) = QoiEncoder::new(&opaque, 2, 1, 4, 0) {
+            let encoder = encoder.with_stats();
+            if let Ok(QoiEncoderProgress::Finished(_, _, stats)) = encoder.process_pixels(&opaque, [0; 8]) {
+                assert!(!stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        }
+        if let Ok((encoder, _)) = QoiEncoder::new(&transparent, 3, 1, 4, 0) {
+            let progress = encoder.with_stats().process_pixels(&transparent, [0; 16]);
+            if let Ok(QoiEncoderProgress::Finished(_, _, stats)) = progress {
+                assert!(stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        }
+        let transparent_black = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]; // same as the starting previously seen pixels
+        if let Ok((encoder, _)) = QoiEncoder::new(&transparent_black, 3, 1, 4, 0) {
+            let progress = encoder.with_stats().process_pixels(&transparent_black, [0; 8]);
+            if let Ok(QoiEncoderProgress::Finished(buffer, empty, stats)) = progress {
+                assert!(empty == 6 && is_identical(buffer.split_at(2).0, &[0, 193])); // Index chunk and Run chunk
+                assert!(stats.alpha_used());
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn infallible_push_pixel() {
+        let encoder = QoiEncoderInternal::new(68, true, 0, 68);
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(0, 0, 0, 255));       // starts run (special 1st run case)
+        assert!(run.is_none() && op.is_none());
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(0, 0, 0, 222));       // finishes run, rgba chunk
+        assert!(matches!(run, Some(QoiOp::Run(1))) && matches!(op, Some(QoiOp::Rgba(0, 0, 0, 222))));
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(0, 0, 0, 255));       // index chunk
+        assert!(run.is_none() && matches!(op, Some(QoiOp::Index(53))));
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(1, 1, 1, 255));       // diff chunk
+        assert!(run.is_none() && matches!(op, Some(QoiOp::Diff(1, 1, 1))));
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(11, 10, 9, 255));     // luma chunk
+        assert!(run.is_none() && matches!(op, Some(QoiOp::Luma(9, 1, -1))));
+        let (mut encoder, run, op) = encoder.push_pixel(Pixel::new(128, 128, 128, 255)); // rgb chunk
+        assert!(run.is_none() && matches!(op, Some(QoiOp::Rgb(128, 128, 128))));
+        let mut index = 0;
+        while index < 61 {
+            let (run, op);
+            (encoder, run, op) = encoder.push_pixel(Pixel::new(128, 128, 128, 255));
+            assert!(run.is_none() && op.is_none());
+            index += 1;
+        }
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(128, 128, 128, 255)); // 62nd pixel finishes run
+        assert!(matches!(run, Some(QoiOp::Run(62))) && op.is_none());
+        let (encoder, run, op) = encoder.push_pixel(Pixel::new(128, 128, 128, 255));
+        assert!(run.is_none() && op.is_none());
+        let (_, run) = encoder.finish_pixels();
+        assert!(matches!(run, Some(QoiOp::Run(1))));
+    }
+    #[test]
     const fn bad_process_pixels_buffer_size() {
         let input = [255, 255, 255, 255,
                      255, 255, 255, 255,
@@ -1,3 +1,5 @@
+use crate::op::QoiOp;
+
 /// The possible errors when decoding or encoding QOI files.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -30,6 +32,44 @@ pub enum QoiError {
     InputHeaderMismatch(u32, u32, u64),
     /// The input data is not divisible by specified channels. Shows total size of input data in bytes and specified channels.
     IncorrectInputData(usize, u8),
+    /// The size of the output array does not match the size required by the image. Shows size of output array in bytes and pixel amount.
+    ArraySizeMismatch(usize, u64),
+    /// The size of the output array does not match the size of the encoded image. Shows size of output array in bytes and size of encoded image in bytes.
+    EncodedSizeMismatch(usize, usize),
+    /// A chunk is missing required bytes and reads into the `8` byte end marker. Shows the offset of the chunk in bytes.
+    ChunkIntoEndMarker(usize),
+    /// The values of the chunk are outside of the range it can store. Shows the chunk.
+    InvalidOp(QoiOp),
+    /// The output buffer does not have enough space left. Shows the required and available space in bytes.
+    NotEnoughSpace(usize, usize),
+    /// The re-encoded chunks do not reproduce the input pixels. Shows the position of the first differing pixel.
+    ReencodeMismatch(u64),
+    /// The amount of colour keys is `0` or more than the encoder can hold (`8`). Shows the amount of colour keys.
+    InvalidColorKeyAmount(usize),
+    /// The palette has no entries or more than `256` entries. Shows the amount of entries.
+    InvalidPaletteSize(usize),
+    /// The bits per index are incorrect. Correct values are: `1`, `2`, `4` or `8`. Shows the encountered value.
+    InvalidIndexBits(u8),
+    /// The row stride is smaller than the bytes required by a row of indices or plane values. Shows the stride and required bytes.
+    InvalidStride(usize, usize),
+    /// An index refers to an entry past the end of the palette. Shows the index and the position of the pixel.
+    PaletteIndexOutOfRange(u8, u64),
+    /// The image has more distinct colours than the palette limit. Shows the limit.
+    TooManyColors(usize),
+    /// A decoded colour is not in the palette. Shows the position of the pixel.
+    ColorNotInPalette(u64),
+    /// A row is wider than the quantizer can dither or the scaler can hold. Shows the width of the row and the widest supported row.
+    ImageTooWide(u32, usize),
+    /// The input does not match the size of a frame with the chosen layout. Shows the size of the input and the frame in bytes.
+    IncorrectFrameSize(usize, u64),
+    /// The output buffer is not divisible by the size of a band of YCbCr (YUV) rows. Shows the size of the output buffer and of a band in bytes.
+    IncorrectBandSize(usize, usize),
+    /// The amount of planes is incorrect. Correct values are: `3` (RGB) or `4` (RGBA). Shows the encountered value.
+    InvalidPlaneAmount(usize),
+    /// A plane is too small for the rows it must hold. Shows the number of the plane, its size and the required size in bytes.
+    PlaneTooSmall(usize, usize, usize),
+    /// The scale factor is `0` or enlarges the image past the largest width or height of a header. Shows the factor.
+    InvalidScaleFactor(u32),
 }
 
 #[allow(clippy::many_single_char_names)]
@@ -38,7 +78,7 @@ impl core::fmt::Display for QoiError {
         match self {
             Self::InputTooSmall(amount) => write!(f, "Insufficient input: must be more than 22 bytes, detected {amount} bytes"),
             Self::InvalidWidthHeight(w, h) => write!(f, "Width or height cannot be 0: detected {w} width and {h} height"),
-            Self::IncorrectBufferSize(size) => write!(f, "Output buffer size for decoder must be divisible by 4, detected buffer size of {size} bytes"),
+            Self::IncorrectBufferSize(size) => write!(f, "Output buffer size for decoder must be divisible by 4 (3 for RGB output), detected buffer size of {size} bytes"),
             Self::BufferTooSmall(size) => write!(f, "Output buffer size for encoder must be at least 5 bytes, detected {size} bytes"),
             Self::InvalidMagicBytes(a, b, c, d) => write!(f, "Invalid magic bytes: {a}, {b}, {c}, {d}"),
             Self::InvalidChannelsValue(v) => write!(f, "Invalid channels value: {v}"),
@@ -50,6 +90,25 @@ impl core::fmt::Display for QoiError {
             Self::IncorrectPixelAmount(h, a) => write!(f, "Malformed input: header specified {h} pixels but only encountered {a} pixels"),
             Self::InputHeaderMismatch(w, h, i) => write!(f, "Specified {w} width and {h} height but input contains {i} pixels."),
             Self::IncorrectInputData(size, channels) => write!(f, "Malformed input: input data of {size} bytes detected which cannot represent {channels} byte pixels"),
+            Self::ArraySizeMismatch(size, pixels) => write!(f, "Output array of {size} bytes does not match the required size for an image of {pixels} pixels"),
+            Self::EncodedSizeMismatch(size, encoded) => write!(f, "Output array of {size} bytes does not match the encoded image size of {encoded} bytes"),
+            Self::ChunkIntoEndMarker(offset) => write!(f, "Malformed input: the chunk at offset {offset} is incomplete and reads into the 8 byte end marker"),
+            Self::InvalidOp(op) => write!(f, "Chunk values are outside of the range the chunk can store: {op:?}"),
+            Self::NotEnoughSpace(required, available) => write!(f, "Output buffer requires {required} bytes of space but only {available} bytes are available"),
+            Self::ReencodeMismatch(position) => write!(f, "Re-encoded chunks do not reproduce the input pixel at position {position}"),
+            Self::InvalidColorKeyAmount(amount) => write!(f, "Between 1 and 8 colour keys are required, detected {amount} colour keys"),
+            Self::InvalidPaletteSize(size) => write!(f, "Palette must have between 1 and 256 entries, detected {size} entries"),
+            Self::InvalidIndexBits(bits) => write!(f, "Invalid bits per index: {bits}"),
+            Self::InvalidStride(stride, required) => write!(f, "Row stride of {stride} bytes is smaller than the {required} bytes required by a row"),
+            Self::PaletteIndexOutOfRange(index, position) => write!(f, "Index {index} of the pixel at position {position} is outside of the palette"),
+            Self::TooManyColors(limit) => write!(f, "Image has more than {limit} distinct colours"),
+            Self::ColorNotInPalette(position) => write!(f, "Colour of the pixel at position {position} is not in the palette"),
+            Self::ImageTooWide(width, widest) => write!(f, "Row of {width} pixels cannot be dithered or scaled, the widest supported row is {widest} pixels"),
+            Self::IncorrectFrameSize(size, frame) => write!(f, "Input of {size} bytes does not match the frame size of {frame} bytes"),
+            Self::IncorrectBandSize(size, band) => write!(f, "Output buffer size for YUV output must be divisible by the band size of {band} bytes, detected buffer size of {size} bytes"),
+            Self::InvalidPlaneAmount(amount) => write!(f, "Invalid amount of planes: {amount}"),
+            Self::PlaneTooSmall(plane, size, required) => write!(f, "Plane {plane} of {size} bytes is smaller than the required {required} bytes"),
+            Self::InvalidScaleFactor(factor) => write!(f, "Invalid scale factor: {factor}"),
         }
     }
 }
@@ -11,6 +11,23 @@ pub struct QoiHeader {
 }
 
 impl QoiHeader {
+    /// Generates a [`QoiHeader`] from the width, height, channels and colorspace values.
+    ///
+    /// This is useful when building a QOI image by hand with [`QoiOp::write`](crate::QoiOp::write).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the following is true:
+    ///
+    /// 1: The width or height values are `0`.\
+    /// 2: The channels value is not `3` (RGB) or `4` (RGBA).\
+    /// 3: The colorspace value is not `0` (sRGB with linear alpha) or `1` (all channels linear).
+    pub const fn new(width: u32, height: u32, channels: u8, colorspace: u8) -> Result<Self, QoiError> {
+        if width == 0 || height == 0 {return Err(QoiError::InvalidWidthHeight(width, height));}
+        if channels != 3 && channels != 4 {return Err(QoiError::InvalidChannelsValue(channels));}
+        if colorspace != 0 && colorspace != 1 {return Err(QoiError::InvalidColorspaceValue(colorspace));}
+        Ok(QoiHeaderInternal::new(width, height, channels, colorspace).public())
+    }
     /// The magic bytes of a QOI image. They should always be "qoif" ([`113`, `111`, `105`, `102`]).
     #[must_use]
     pub const fn magic_bytes(&self) -> [u8; 4] {
@@ -36,6 +53,29 @@ impl QoiHeader {
     pub const fn colorspace(&self) -> u8 {
         self.data.colorspace
     }
+    /// The total amount of pixels in a QOI image.
+    ///
+    /// The maximum value is [`u32::MAX`] multiplied by [`u32::MAX`] which always fits within a [`u64`].
+    #[must_use]
+    pub const fn pixel_amount(&self) -> u64 {
+        self.data.width as u64 * self.data.height as u64
+    }
+    /// The size in bytes of the decoded image when each pixel takes up `bytes_per_pixel` bytes of output.
+    ///
+    /// The decoder outputs `4` byte pixels (RGBA) so use `4` to size a buffer for the whole image.
+    ///
+    /// Returns `None` if the size does not fit within a [`usize`] on the target architecture.
+    #[must_use]
+    pub const fn decoded_size(&self, bytes_per_pixel: usize) -> Option<usize> {
+        decoded_size(self.data.width, self.data.height, bytes_per_pixel)
+    }
+    /// The largest possible size in bytes of a QOI image with the same width, height and channels as this header.
+    ///
+    /// See [`max_encoded_size`] for details.
+    #[must_use]
+    pub const fn max_encoded_size(&self) -> Option<usize> {
+        max_encoded_size(self.data.width, self.data.height, self.data.channels)
+    }
     /// Convert the header to an array of bytes.
     ///
     /// A convenience method for extracting all the bytes from the header.
@@ -63,6 +103,33 @@ impl QoiHeader {
     }
 }
 
+/// The size in bytes of a decoded image of `width` by `height` pixels with `bytes_per_pixel` bytes per pixel.
+///
+/// Returns `None` if the size does not fit within a [`usize`] on the target architecture.
+#[allow(clippy::cast_possible_truncation)] // pixel amount checked to fit within usize before casting
+#[must_use]
+pub const fn decoded_size(width: u32, height: u32, bytes_per_pixel: usize) -> Option<usize> {
+    let pixel_amount = width as u64 * height as u64;
+    if pixel_amount > usize::MAX as u64 {return None;}
+    (pixel_amount as usize).checked_mul(bytes_per_pixel)
+}
+
+/// The largest possible size in bytes of an encoded QOI image including the `14` byte header and `8` byte end marker.
+///
+/// The worst case is every pixel being encoded as the largest chunk available for the input pixel data.
+/// For `4` byte pixels (RGBA) that is a `5` byte RGBA chunk and for `3` byte pixels (RGB) that is a `4` byte RGB chunk.
+/// The channels value must describe the input pixel data given to the encoder.
+///
+/// Returns `None` if channels is not `3` or `4` or the size does not fit within a [`usize`] on the target architecture.
+#[must_use]
+pub const fn max_encoded_size(width: u32, height: u32, channels: u8) -> Option<usize> {
+    if channels != 3 && channels != 4 {return None;}
+    match decoded_size(width, height, channels as usize + 1) {
+        Some(size) => size.checked_add(14 + 8),
+        None => None,
+    }
+}
+
 pub struct QoiHeaderInternal {
     pub magic_bytes: [u8; 4],
     pub width: u32,
@@ -99,7 +166,7 @@ impl QoiHeaderInternal {
 #[cfg(test)]
 mod tests {
     use crate::{error::QoiError, utils::is_identical};
-    use super::{QoiHeader, QoiHeaderInternal};
+    use super::{decoded_size, max_encoded_size, QoiHeader, QoiHeaderInternal};
     #[test]
     const fn infallible_new() {
         let (width, height, channels, colorspace) = (2, 2, 4, 0);
@@ -138,6 +205,30 @@ mod tests {
         }
     }
     #[test]
+    const fn good_public_new() {
+        let header = QoiHeader::new(2, 4, 3, 1);
+        assert!(header.is_ok());
+        if let Ok(header) = header {
+            assert!(is_identical(&header.to_u8(), &[113, 111, 105, 102, 0, 0, 0, 2, 0, 0, 0, 4, 3, 1]));
+        }
+        assert!(matches!(QoiHeader::new(0, 4, 3, 1), Err(QoiError::InvalidWidthHeight(0, 4))));
+        assert!(matches!(QoiHeader::new(2, 4, 2, 1), Err(QoiError::InvalidChannelsValue(2))));
+        assert!(matches!(QoiHeader::new(2, 4, 3, 2), Err(QoiError::InvalidColorspaceValue(2))));
+    }
+    #[test]
+    const fn infallible_sizes() {
+        let header = QoiHeaderInternal::new(3, 5, 3, 0).public();
+        assert!(header.pixel_amount() == 15);
+        assert!(matches!(header.decoded_size(4), Some(60)));
+        assert!(matches!(header.decoded_size(3), Some(45)));
+        assert!(matches!(header.max_encoded_size(), Some(82))); // 14 + 15 * 4 + 8
+        assert!(matches!(max_encoded_size(3, 5, 4), Some(97)));  // 14 + 15 * 5 + 8
+        assert!(max_encoded_size(3, 5, 2).is_none());
+        assert!(decoded_size(u32::MAX, u32::MAX, 4).is_none());
+        let big = QoiHeaderInternal::new(u32::MAX, u32::MAX, 4, 0).public();
+        assert!(big.pixel_amount() == u32::MAX as u64 * u32::MAX as u64);
+    }
+    #[test]
     const fn good_extract() {
         let input = [113, 111, 105, 102,      // magic bytes
                      0, 0, 0, 2,              // width
@@ -7,7 +7,8 @@
 //! ## Motivation
 //!
 //! I wanted to understand the [QOI specification] and implement a decoder and encoder in a [const context].
-//! Every public and private function is const including all the tests.
+//! Every public and private function is const apart from trait implementations and the formatting they rely on.
+//! The same goes for the tests where only the ones checking formatted text are not const.
 //! This project helped me to better understand bitwise operations.
 //!
 //! ## Usage
@@ -24,15 +25,19 @@
 //! Be careful using the width and height values from the header when calculating the pixel amount.
 //! The [QOI specification] states they are stored as unsigned 32bit integers in the header.
 //! This makes the maximum size of a QOI image in pixels [`u32::MAX`] multiplied by [`u32::MAX`].
-//! The result of that calculation will safely fit within a [`u64`].
+//! The result of that calculation will safely fit within a [`u64`] and is available from [`QoiHeader::pixel_amount`].
 //! Also keep in mind that casting values to [`usize`] may cause truncation depending on the target architecture.
-//! You should always perform proper error handling when converting or casting between integer types.
+//! [`QoiHeader::decoded_size`] performs the calculation for you and returns `None` if the result would not fit.
 //!
 //! ```
-//! let (mut decoder, header) = QoiDecoder::init(input)?;
-//! if let Some(pixel_amount) = (header.width() as usize).checked_mul(header.height() as usize) {
-//!     // 1 pixel is 4 bytes (red, green, blue, alpha)
-//!     let mut output = Vec::with_capacity(pixel_amount * 4); // usize may truncate
+//! # use const_qoi::{QoiDecoder, QoiDecoderProgress};
+//! # fn main() -> Result<(), const_qoi::QoiError> {
+//! # let input: &[u8] = &[113, 111, 105, 102, 0, 0, 0, 2, 0, 0, 0, 4, 4, 0, 255, 255, 255, 255, 255, 198,
+//! #                      0, 0, 0, 0, 0, 0, 0, 1];
+//! let (mut decoder, header) = QoiDecoder::new(input)?;
+//! // 1 pixel is 4 bytes (red, green, blue, alpha)
+//! if let Some(size) = header.decoded_size(4) {
+//!     let mut output = Vec::with_capacity(size);
 //!     loop {
 //!         match decoder.process_chunks(input, [0; 1024])? {
 //!             QoiDecoderProgress::Unfinished((dec, buffer)) => {
@@ -41,7 +46,7 @@
 //!                     .into_iter()
 //!                     .for_each(|byte| output.push(byte));
 //!             },
-//!             QoiDecoderProgress::Finished((buffer, empty)) => {
+//!             QoiDecoderProgress::Finished((buffer, empty, _stats)) => {
 //!                 buffer
 //!                     .into_iter()
 //!                     .take(buffer.len() - empty) // output buffer may not be full
@@ -51,16 +56,26 @@
 //!         }
 //!     }
 //!     // output is now filled with 4 byte pixel (RGBA) values
+//! #   assert_eq!(output, [255; 32]);
 //! }
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ### Encoding
 //!
 //! Below is an example of a simple encoder.
 //!
+//! The size of the encoded image depends on the pixel data.
+//! [`max_encoded_size`] calculates the worst case size which can be used to allocate once or to size a static buffer.
+//!
 //! ```
+//! # use const_qoi::{max_encoded_size, QoiEncoder, QoiEncoderProgress};
+//! # fn main() -> Result<(), const_qoi::QoiError> {
+//! # let (width, height, channels, colorspace) = (2, 2, 4, 0);
+//! # let input: &[u8] = &[0, 0, 0, 0, 1, 1, 1, 0, 255, 255, 255, 255, 255, 255, 255, 255];
 //! let (mut encoder, header) = QoiEncoder::new(input, width, height, channels, colorspace)?;
-//! let mut output = Vec::new();
+//! let mut output = Vec::with_capacity(max_encoded_size(width, height, channels).unwrap_or(0));
 //! header.to_u8().into_iter().for_each(|byte| output.push(byte)); // adding 14 byte header
 //! loop {
 //!     match encoder.process_pixels(input, [0; 1000])? {
@@ -71,7 +86,7 @@
 //!                 .take(buffer.len() - empty)
 //!                 .for_each(|byte| output.push(byte));
 //!         },
-//!         QoiEncoderProgress::Finished(buffer, empty) => {
+//!         QoiEncoderProgress::Finished(buffer, empty, _stats) => {
 //!             buffer
 //!                 .into_iter()
 //!                 .take(buffer.len() - empty)
@@ -84,6 +99,91 @@
 //!     }
 //! }
 //! // output is now a valid QOI image ready to be written to a file
+//! # assert_eq!(output.len(), 14 + 8 + 8);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The output always decodes to the input pixels but may differ slightly from other encoders.
+//! Call [`QoiEncoder::with_reference_parity`] before processing for output that is byte for byte identical to the
+//! reference `qoi.h` encoder.
+//!
+//! ### Statistics
+//!
+//! Both the decoder and the encoder can count the QOI data chunks they process by calling `with_stats` before
+//! processing.
+//! The resulting [`QoiStats`] are returned when finished and include the amount of each chunk, the bytes spent on them,
+//! the longest run, the hit rate of the previously seen pixels array and the overall compression ratio.
+//!
+//! ### Pixel conversions
+//!
+//! QOI images store straight alpha. Calling `with_premultiplied_alpha` on the decoder premultiplies the output pixels
+//! and calling it on the encoder accepts premultiplied input pixels. [`QoiPremultiply`] decides whether sRGB colour
+//! values are multiplied directly or in linear light.
+//!
+//! The colorspace value of the header decides how linear light values are converted.
+//! [`QoiDecoder::process_chunks_linear_u16`] and [`QoiDecoder::process_chunks_linear_f32`] output linear light RGBA
+//! values ready to be used as textures while [`QoiEncoder::process_pixels_linear_u16`] and
+//! [`QoiEncoder::process_pixels_linear_f32`] accept them as input. The sRGB conversions use lookup tables built at
+//! compile time.
+//!
+//! Pixels with more than 8 bits of precision can be encoded with [`QoiEncoder::process_pixels_u16`] and
+//! [`QoiEncoder::process_pixels_f32`]. [`QoiReduction`] decides whether they are truncated, rounded or dithered to
+//! avoid banding in smooth gradients. Floating point values outside of `0.0..=1.0` are clamped and NaN becomes `0`.
+//!
+//! [`QoiDecoder::with_flattened_alpha`] composites transparent pixels over a solid colour or a checkerboard described
+//! by [`QoiBackground`] in sRGB or linear light. Together with [`QoiDecoder::with_rgb_output`] the decoder writes `3`
+//! byte pixels for displays and formats without alpha.
+//!
+//! Indexed images are encoded with [`QoiEncoder::new_indexed`] and [`QoiEncoder::process_pixels_indexed`] which look
+//! up every colour in the palette while encoding. Indices can be `8` bits or packed into `1`, `2` or `4` bits with a
+//! row stride.
+//!
+//! Images with at most `256` colours can be decoded to a palette and `8` bit indices for palette hardware.
+//! [`QoiDecoder::collect_palette`] scans the image for its colours in order of first appearance and
+//! [`QoiDecoder::process_chunks_indexed`] then decodes it again writing an index for every pixel.
+//!
+//! Images with more colours are reduced to a palette of a chosen size by [`QoiQuantizer`] using median cut with
+//! optional Floyd-Steinberg dithering. The image is decoded several times so only a small fixed amount of memory is
+//! used.
+//!
+//! Video frames are read and written as YCbCr (YUV) values in the layouts of [`QoiYuvLayout`] with the BT.601 or
+//! BT.709 matrix of [`QoiYuvMatrix`]. [`QoiDecoder::process_chunks_yuv`] outputs bands of rows sharing chroma values
+//! while [`QoiEncoder::new_yuv`] and [`QoiEncoder::process_pixels_yuv`] convert every pixel of a frame while encoding.
+//!
+//! Planar (CHW) buffers with a separate plane per channel are read with [`QoiEncoder::new_planar`] and
+//! [`QoiEncoder::process_pixels_planar`] and written with [`QoiDecoder::process_chunks_planar`]. Every plane has its
+//! own row stride and the alpha plane is optional.
+//!
+//! [`QoiScaler`] shrinks an image by an integer factor for thumbnails, averaging boxes of pixels in linear light for
+//! sRGB images, or enlarges pixel art by repeating every pixel. Only `1` row is kept so large images can be scaled
+//! without decoding them into memory first.
+//!
+//! Sprites using a magic colour for transparency are encoded with [`QoiEncoder::new_color_keyed`] which turns every
+//! pixel matching a colour key into a fully transparent pixel. [`QoiDecoder::with_color_key`] turns them back into the
+//! key colour.
+//!
+//! ### Working with chunks
+//!
+//! [`QoiTokenizer`] reads the QOI data chunks of an image as [`QoiOp`] values without calculating any pixels.
+//! [`QoiOp::write`] does the opposite and can be used with [`QoiHeader::new`] and [`END_MARKER`] to build an image by
+//! hand. [`QoiDisassembly`] prints every chunk with its offset and resulting pixel which helps when debugging images
+//! from other encoders. [`QoiLinter`] flags chunks from other encoders that are valid but wasteful and calculates how
+//! many bytes encoding the image with this crate would save. [`QoiReencoder`] then produces those smaller chunks a few
+//! pixels at a time without decoding the whole image and checks that every pixel is unchanged.
+//!
+//! ### Compile time decoding and encoding
+//!
+//! [`decode_to_array`] decodes a whole image into an array and can be used in `const` and `static` items.
+//! The [`include_qoi`] macro calculates the size of the array from the header so the image is stored decoded in the
+//! binary without any runtime cost.
+//!
+//! [`encode_to_array`] does the opposite and encodes pixel data into an array containing a complete QOI image.
+//! The exact size of the array is calculated by [`encoded_size`] and the [`encode_qoi`] macro does this for you.
+//!
+//! ```ignore
+//! const_qoi::include_qoi!(pub static LOGO = include_bytes!("logo.qoi"));
+//! const_qoi::encode_qoi!(pub static GRADIENT = &GRADIENT_PIXELS, 256, 1, 3, 0);
 //! ```
 //!
 //! [alloc]: <https://doc.rust-lang.org/alloc/index.html>
@@ -92,15 +192,34 @@
 #![no_std]
 #![forbid(unsafe_code)]
 
+mod color;
 mod consts;
 mod decoder;
+mod disasm;
+mod embed;
 mod encoder;
 mod error;
 mod header;
+mod lint;
+mod op;
 mod pixel;
+mod quantize;
+mod reencode;
+mod scale;
+mod stats;
 mod utils;
 
-pub use crate::decoder::{QoiDecoder, QoiDecoderProgress};
-pub use crate::encoder::{QoiEncoder, QoiEncoderProgress};
+pub use crate::consts::END_MARKER;
+pub use crate::color::{QoiBackground, QoiPremultiply, QoiReduction, QoiYuvLayout, QoiYuvMatrix};
+pub use crate::decoder::{QoiDecoder, QoiDecoderProgress, QoiPlanarProgress, QoiValidationReport};
+pub use crate::disasm::QoiDisassembly;
+pub use crate::embed::{decode_to_array, encode_to_array, encoded_size};
+pub use crate::encoder::{QoiEncoder, QoiEncoderProgress, QoiTransparentPixels};
 pub use crate::error::QoiError;
-pub use crate::header::QoiHeader;
+pub use crate::header::{decoded_size, max_encoded_size, QoiHeader};
+pub use crate::lint::{QoiLintIssue, QoiLintKind, QoiLintSummary, QoiLinter};
+pub use crate::op::{QoiOp, QoiTokenizer};
+pub use crate::quantize::{QoiQuantizer, QoiQuantizerProgress};
+pub use crate::reencode::{QoiReencoder, QoiReencoderProgress};
+pub use crate::scale::{QoiScaler, QoiScalerProgress};
+pub use crate::stats::QoiStats;
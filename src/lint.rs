@@ -0,0 +1,308 @@
+use crate::{
+    consts::{DEFAULT_PIXEL, ZERO_PIXEL},
+    decoder::check_progress,
+    encoder::QoiEncoderInternal,
+    error::QoiError,
+    header::QoiHeader,
+    op::{QoiOp, QoiTokenizer},
+    pixel::Pixel,
+};
+
+/// The reason a chunk was flagged by the [`QoiLinter`].
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiLintKind {
+    /// The chunk repeats the previous pixel which could have been part of a run chunk.
+    PreviousPixel,
+    /// The RGBA chunk has the same alpha value as the previous pixel so a smaller chunk could have been used.
+    UnchangedAlpha,
+    /// The run chunk follows a run chunk that was shorter than the maximum of `62` pixels.
+    SplitRun,
+    /// The pixel could have been stored in the smaller chunk shown.
+    LargerThanNeeded(QoiOp),
+}
+
+/// A chunk flagged by the [`QoiLinter`] as non-canonical or wasteful.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QoiLintIssue {
+    offset: usize,
+    op: QoiOp,
+    kind: QoiLintKind,
+}
+
+impl QoiLintIssue {
+    /// The offset in bytes of the chunk from the start of the input.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// The flagged chunk.
+    #[must_use]
+    pub const fn op(&self) -> QoiOp {
+        self.op
+    }
+    /// The reason the chunk was flagged.
+    #[must_use]
+    pub const fn kind(&self) -> QoiLintKind {
+        self.kind
+    }
+}
+
+/// The totals of a finished [`QoiLinter`].
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QoiLintSummary {
+    size: usize,
+    canonical_size: usize,
+    issues: u64,
+}
+
+impl QoiLintSummary {
+    /// The size in bytes of the linted QOI image.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+    /// The size in bytes of the same image encoded by the [`QoiEncoder`](crate::QoiEncoder).
+    #[must_use]
+    pub const fn canonical_size(&self) -> usize {
+        self.canonical_size
+    }
+    /// The amount of bytes saved by encoding the image with the [`QoiEncoder`](crate::QoiEncoder).
+    ///
+    /// This is `0` if the linted image is already the same size or smaller.
+    #[must_use]
+    pub const fn saved_bytes(&self) -> usize {
+        self.size.saturating_sub(self.canonical_size)
+    }
+    /// The amount of flagged chunks.
+    #[must_use]
+    pub const fn issue_amount(&self) -> u64 {
+        self.issues
+    }
+}
+
+/// An analyser that decodes a QOI image and flags chunks that are valid but non-canonical or wasteful.
+///
+/// Different encoders can produce different chunks for the same pixels.
+/// The linter compares every chunk with the chunk the [`QoiEncoder`](crate::QoiEncoder) would choose for the same
+/// pixel and flags the chunk if it is larger or prevents a run.
+/// It can be driven by calling [`QoiLinter::next_issue`] in a loop or used as an [`Iterator`].
+///
+/// While linting the pixels are also encoded by the [`QoiEncoder`](crate::QoiEncoder) rules to calculate how many
+/// bytes a canonical encoding would save. This is available from [`QoiLinter::summary`] once all chunks are processed.
+#[allow(clippy::module_name_repetitions)]
+pub struct QoiLinter<'a> {
+    tokenizer: QoiTokenizer<'a>,
+    input: &'a [u8],
+    seen_pixels: [Pixel; 64],
+    previous_pixel: Pixel,
+    previous_run: u8,            // length of the previous chunk if it was a run chunk, otherwise 0
+    pixel_amount: u64,           // keeps track of pixels to process, always decrements
+    expected_pixels: u64,        // total size of image in pixels, does not change
+    byte_index: usize,           // index after the last processed chunk
+    canonical: QoiEncoderInternal, // encodes the decoded pixels by the rules of the QoiEncoder
+    canonical_size: usize,       // size of the chunks generated by the canonical encoder
+    issues: u64,
+    summary: Option<QoiLintSummary>,
+    done: bool,                  // set once all chunks are processed or an error occurs
+}
+
+impl<'a> QoiLinter<'a> {
+    /// Generates a [`QoiLinter`] and a [`QoiHeader`] from the input bytes of a QOI image.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::new`](crate::QoiDecoder::new).
+    pub const fn new(input: &'a [u8]) -> Result<(Self, QoiHeader), QoiError> {
+        let (tokenizer, header) = match QoiTokenizer::new(input) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let linter = Self {
+            tokenizer,
+            input,
+            seen_pixels: [ZERO_PIXEL; 64],
+            previous_pixel: DEFAULT_PIXEL,
+            previous_run: 0,
+            pixel_amount: header.pixel_amount(),
+            expected_pixels: header.pixel_amount(),
+            byte_index: 14,
+            canonical: QoiEncoderInternal::new(header.pixel_amount(), header.channels() == 4, header.colorspace(),
+                                               header.width()),
+            canonical_size: 0,
+            issues: 0,
+            summary: None,
+            done: false,
+        };
+        Ok((linter, header))
+    }
+    /// Returns the next flagged chunk or `None` when all chunks are processed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Some(Err)` if the input data is malformed in the same ways as for
+    /// [`QoiDecoder::process_chunks`](crate::QoiDecoder::process_chunks).
+    /// No further chunks will be returned afterwards.
+    pub const fn next_issue(&mut self) -> Option<Result<QoiLintIssue, QoiError>> {
+        if self.done {return None;}
+        while let Some(chunk) = self.tokenizer.next_op() {
+            let (offset, op) = match chunk {
+                Ok(both) => both,
+                Err(e) => {self.done = true; return Some(Err(e));},
+            };
+            let pixels = op.pixel_amount() as u64;
+            if pixels > self.pixel_amount { // header understates pixels
+                self.done = true;
+                return Some(Err(QoiError::MoreDataBeforeEnd(self.expected_pixels, (self.input.len() - 8) - offset)));
+            }
+            self.byte_index = offset + op.size();
+            let pixel = op.apply(self.previous_pixel, &self.seen_pixels);
+            let kind = self.check(op, pixel);
+            let mut pushed = 0;
+            while pushed != pixels {
+                let (run, chunk);
+                (self.canonical, run, chunk) = self.canonical.push_pixel(pixel);
+                if let Some(run) = run {self.canonical_size += run.size();}
+                if let Some(chunk) = chunk {self.canonical_size += chunk.size();}
+                pushed += 1;
+            }
+            self.previous_run = if let QoiOp::Run(run) = op {run} else {0};
+            self.seen_pixels[pixel.calculate_hash_index()] = pixel;
+            self.previous_pixel = pixel;
+            self.pixel_amount -= pixels;
+            if let Some(kind) = kind {
+                self.issues += 1;
+                return Some(Ok(QoiLintIssue {offset, op, kind}));
+            }
+        }
+        self.done = true;
+        if let Err(e) = check_progress(self.input, self.byte_index, self.pixel_amount, self.expected_pixels) {
+            return Some(Err(e));
+        }
+        let (_, run) = self.canonical.finish_pixels();
+        if let Some(run) = run {self.canonical_size += run.size();}
+        self.summary = Some(QoiLintSummary {size: self.input.len(), canonical_size: self.canonical_size + 14 + 8, issues: self.issues});
+        None
+    }
+    /// The totals of the linter. Only available once all chunks are processed without errors.
+    #[must_use]
+    pub const fn summary(&self) -> Option<QoiLintSummary> {
+        self.summary
+    }
+    // compares the chunk with the chunk the canonical encoder would choose for the pixel
+    const fn check(&self, op: QoiOp, pixel: Pixel) -> Option<QoiLintKind> {
+        if let QoiOp::Run(_) = op {
+            if self.previous_run != 0 && self.previous_run < 62 {return Some(QoiLintKind::SplitRun);}
+            return None;
+        }
+        if pixel.is_same(self.previous_pixel) {return Some(QoiLintKind::PreviousPixel);}
+        match self.canonical.chunk(pixel) { // the canonical encoder has seen the same pixels so far
+            Some(best) if best.size() < op.size() => {
+                if let (QoiOp::Rgba(..), QoiOp::Rgb(..)) = (op, best) {return Some(QoiLintKind::UnchangedAlpha);}
+                Some(QoiLintKind::LargerThanNeeded(best))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Iterator for QoiLinter<'_> {
+    type Item = Result<QoiLintIssue, QoiError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_issue()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::QoiError, op::QoiOp};
+    use super::{QoiLintKind, QoiLinter};
+    #[test]
+    const fn good_next_issue() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 9,              // width (4xu8 into 1xu32 big endian: 9)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 200, 10, 10, 255,   // RGBA chunk, alpha unchanged (RGB would do)
+                     255, 201, 11, 11, 255,   // RGBA chunk, diff would do
+                     192,                     // Run chunk (amount 1)
+                     193,                     // Run chunk (amount 2), split from previous run
+                     254, 200, 10, 10,        // RGB chunk, index would do
+                     5,                       // Index chunk, same as previous pixel
+                     160, 136,                // Luma chunk, no change from previous pixel
+                     65,                      // Diff chunk (r-2, g-2, b-1)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let linter = QoiLinter::new(&input);
+        assert!(linter.is_ok());
+        if let Ok((mut linter, _)) = linter {
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 14);
+                assert!(matches!(issue.op(), QoiOp::Rgba(200, 10, 10, 255)));
+                assert!(matches!(issue.kind(), QoiLintKind::UnchangedAlpha));
+            }
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 19);
+                assert!(matches!(issue.kind(), QoiLintKind::LargerThanNeeded(QoiOp::Diff(1, 1, 1))));
+            }
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 25);
+                assert!(matches!(issue.kind(), QoiLintKind::SplitRun));
+            }
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 26);
+                assert!(matches!(issue.kind(), QoiLintKind::LargerThanNeeded(QoiOp::Index(5))));
+            }
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 30);
+                assert!(matches!(issue.kind(), QoiLintKind::PreviousPixel));
+            }
+            let issue = linter.next_issue();
+            assert!(matches!(issue, Some(Ok(_))));
+            if let Some(Ok(issue)) = issue {
+                assert!(issue.offset() == 31);
+                assert!(matches!(issue.kind(), QoiLintKind::PreviousPixel));
+            }
+            assert!(linter.next_issue().is_none());
+            assert!(linter.next_issue().is_none());
+            let summary = linter.summary();
+            assert!(summary.is_some());
+            if let Some(summary) = summary {
+                assert!(summary.size() == 42);
+                assert!(summary.canonical_size() == 31); // RGB, DIFF, RUN(3), INDEX, RUN(2), DIFF
+                assert!(summary.saved_bytes() == 11);
+                assert!(summary.issue_amount() == 6);
+            }
+        }
+    }
+    #[test]
+    const fn bad_next_issue() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 255, 255, 255, 100, // RGBA chunk
+                                              // missing chunks: header stated 8 pixels only found 1
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let linter = QoiLinter::new(&input);
+        assert!(linter.is_ok());
+        if let Ok((mut linter, _)) = linter {
+            assert!(matches!(linter.next_issue(), Some(Err(QoiError::IncorrectPixelAmount(8, 1)))));
+            assert!(linter.next_issue().is_none());
+            assert!(linter.summary().is_none());
+        }
+    }
+}
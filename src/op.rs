@@ -0,0 +1,351 @@
+use crate::{
+    decoder::QoiDecoder,
+    error::QoiError,
+    header::QoiHeader,
+    pixel::Pixel,
+};
+
+/// A single QOI data chunk with its values.
+///
+/// The difference values are stored without their bias so a `Diff` of `-1` is a difference of `-1` rather than the
+/// stored value of `1`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QoiOp {
+    /// `QOI_OP_RGB`: the red, green and blue values. The alpha value is unchanged from the previous pixel.
+    Rgb(u8, u8, u8),
+    /// `QOI_OP_RGBA`: the red, green, blue and alpha values.
+    Rgba(u8, u8, u8, u8),
+    /// `QOI_OP_INDEX`: the position (`0..=63`) in the array of previously seen pixels.
+    Index(u8),
+    /// `QOI_OP_DIFF`: the red, green and blue differences (`-2..=1`) from the previous pixel.
+    Diff(i8, i8, i8),
+    /// `QOI_OP_LUMA`: the green difference (`-32..=31`) from the previous pixel followed by the red and blue differences
+    /// minus the green difference (`-8..=7`).
+    Luma(i8, i8, i8),
+    /// `QOI_OP_RUN`: the amount of times (`1..=62`) the previous pixel is repeated.
+    Run(u8),
+}
+
+impl QoiOp {
+    /// The size in bytes of the chunk including the tag.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        match self {
+            Self::Rgb(..) => 4,
+            Self::Rgba(..) => 5,
+            Self::Index(_) | Self::Diff(..) | Self::Run(_) => 1,
+            Self::Luma(..) => 2,
+        }
+    }
+    /// The amount of pixels the chunk represents.
+    #[must_use]
+    pub const fn pixel_amount(&self) -> u8 {
+        match self {
+            Self::Run(run) => *run,
+            _ => 1,
+        }
+    }
+    /// Converts the chunk to its bytes. Returns the bytes and the amount of them that make up the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if any value is outside of the range the chunk can store:
+    ///
+    /// 1: `Index` must be `0..=63`.\
+    /// 2: `Diff` differences must be `-2..=1`.\
+    /// 3: `Luma` green difference must be `-32..=31` and the other differences must be `-8..=7`.\
+    /// 4: `Run` must be `1..=62`.
+    #[allow(clippy::cast_sign_loss)] // values are checked to be within range and biased to be positive
+    pub const fn to_bytes(self) -> Result<([u8; 5], usize), QoiError> {
+        let bytes = match self {
+            Self::Rgb(red, green, blue) => [254, red, green, blue, 0],
+            Self::Rgba(red, green, blue, alpha) => [255, red, green, blue, alpha],
+            Self::Index(index) => {
+                if index > 63 {return Err(QoiError::InvalidOp(self));}
+                [index, 0, 0, 0, 0] // QOI_OP_INDEX: 2bit tag (00), 6bit val (000000)
+            },
+            Self::Diff(red, green, blue) => {
+                if !is_within(red, -2, 1) || !is_within(green, -2, 1) || !is_within(blue, -2, 1) {
+                    return Err(QoiError::InvalidOp(self));
+                }
+                let (red, green, blue) = ((red + 2) as u8, (green + 2) as u8, (blue + 2) as u8); // add bias of 2
+                [64 | red << 4 | green << 2 | blue, 0, 0, 0, 0] // QOI_OP_DIFF: 2bit tag (01), 3x2bit vals (00)
+            },
+            Self::Luma(green, red_green, blue_green) => {
+                if !is_within(green, -32, 31) || !is_within(red_green, -8, 7) || !is_within(blue_green, -8, 7) {
+                    return Err(QoiError::InvalidOp(self));
+                }
+                let (green, red, blue) = ((green + 32) as u8, (red_green + 8) as u8, (blue_green + 8) as u8); // add biases
+                [128 | green, red << 4 | blue, 0, 0, 0] // QOI_OP_LUMA: 2bit tag (10), 6bit val, 2x4bit vals
+            },
+            Self::Run(run) => {
+                if run == 0 || run > 62 {return Err(QoiError::InvalidOp(self));}
+                [0xc0 | (run - 1), 0, 0, 0, 0] // QOI_OP_RUN: 2bit tag (11), 6bit val, bias -1 (0 means a run of 1)
+            },
+        };
+        Ok((bytes, self.size()))
+    }
+    /// Writes the chunk into the output buffer starting at the output index.
+    /// Returns the output buffer and the output index moved past the written chunk.
+    ///
+    /// Together with [`QoiHeader::new`], [`QoiHeader::to_u8`] and [`END_MARKER`](crate::END_MARKER) this can be used to
+    /// build a complete QOI image by hand.
+    /// Be aware that the chunks are written as they are so it is up to you to make sure they produce the intended pixels.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is not enough space left in the output buffer or under the same conditions as
+    /// [`QoiOp::to_bytes`].
+    pub const fn write<const N: usize>(self,
+                                      mut output: [u8; N],
+                                      mut output_index: usize) -> Result<([u8; N], usize), QoiError> {
+        let (bytes, size) = match self.to_bytes() {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let space = N.saturating_sub(output_index);
+        if space < size {return Err(QoiError::NotEnoughSpace(size, space));}
+        let mut index = 0;
+        while index != size {
+            output[output_index] = bytes[index];
+            output_index += 1;
+            index += 1;
+        }
+        Ok((output, output_index))
+    }
+    // calculates the pixel produced by the chunk from the previous pixel and the previously seen pixels
+    #[allow(clippy::cast_sign_loss)] // wrapping arithmetic on the two's complement representation is intended
+    pub(crate) const fn apply(self, previous: Pixel, seen_pixels: &[Pixel; 64]) -> Pixel {
+        match self {
+            Self::Rgb(red, green, blue) => Pixel::new(red, green, blue, previous.alpha),
+            Self::Rgba(red, green, blue, alpha) => Pixel::new(red, green, blue, alpha),
+            Self::Index(index) => seen_pixels[index as usize & 0x3f],
+            Self::Diff(red, green, blue) => Pixel::new(previous.red.wrapping_add(red as u8),
+                                                       previous.green.wrapping_add(green as u8),
+                                                       previous.blue.wrapping_add(blue as u8),
+                                                       previous.alpha),
+            Self::Luma(green, red_green, blue_green) => {
+                let red = green.wrapping_add(red_green) as u8;
+                let blue = green.wrapping_add(blue_green) as u8;
+                Pixel::new(previous.red.wrapping_add(red),
+                           previous.green.wrapping_add(green as u8),
+                           previous.blue.wrapping_add(blue),
+                           previous.alpha)
+            },
+            Self::Run(_) => previous,
+        }
+    }
+    // reads the chunk starting at the byte index. the input must contain at least 5 bytes from the byte index
+    #[allow(clippy::cast_possible_wrap)] // values are masked and unbiased so always fit within i8
+    pub(crate) const fn from_input(input: &[u8], byte_index: usize) -> Self {
+        let tag = input[byte_index];
+        match tag {
+            254 => Self::Rgb(input[byte_index + 1], input[byte_index + 2], input[byte_index + 3]),
+            255 => Self::Rgba(input[byte_index + 1], input[byte_index + 2], input[byte_index + 3], input[byte_index + 4]),
+            0..=63 => Self::Index(tag),
+            64..=127 => Self::Diff(((tag >> 4) & 0x03) as i8 - 2, ((tag >> 2) & 0x03) as i8 - 2, (tag & 0x03) as i8 - 2),
+            128..=191 => {
+                let red_blue = input[byte_index + 1];
+                Self::Luma((tag & 0x3f) as i8 - 32, ((red_blue >> 4) & 0x0f) as i8 - 8, (red_blue & 0x0f) as i8 - 8)
+            },
+            192..=253 => Self::Run((tag & 0x3f) + 1),
+        }
+    }
+}
+
+#[inline]
+const fn is_within(value: i8, min: i8, max: i8) -> bool {
+    value >= min && value <= max
+}
+
+/// A tokenizer for the QOI data chunks of a QOI image.
+///
+/// The tokenizer only reads the chunks and their values without calculating any pixels.
+/// It can be driven by calling [`QoiTokenizer::next_op`] in a loop or used as an [`Iterator`].
+/// Every chunk is returned with its offset in bytes from the start of the input.
+///
+/// The header and end marker are checked when creating the tokenizer.
+/// The amount of pixels represented by the chunks is not compared with the header, use
+/// [`QoiDecoder::validate`] for that.
+#[allow(clippy::module_name_repetitions)]
+pub struct QoiTokenizer<'a> {
+    input: &'a [u8],
+    byte_index: usize, // keeps track of input index, always increments
+}
+
+impl<'a> QoiTokenizer<'a> {
+    /// Generates a [`QoiTokenizer`] and a [`QoiHeader`] from the input bytes of a QOI image.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::new`].
+    pub const fn new(input: &'a [u8]) -> Result<(Self, QoiHeader), QoiError> {
+        match QoiDecoder::new(input) {
+            Ok((_, header)) => Ok((Self {input, byte_index: 14}, header)),
+            Err(e) => Err(e),
+        }
+    }
+    /// Returns the next chunk and its offset or `None` when the end marker is reached.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Some(Err)` if the chunk is missing required bytes and reads into the end marker.
+    /// No further chunks will be returned afterwards.
+    pub const fn next_op(&mut self) -> Option<Result<(usize, QoiOp), QoiError>> {
+        let end = self.input.len() - 8;
+        if self.byte_index >= end {return None;}
+        let offset = self.byte_index;
+        let op = QoiOp::from_input(self.input, offset);
+        if offset + op.size() > end {
+            self.byte_index = end;
+            return Some(Err(QoiError::ChunkIntoEndMarker(offset)));
+        }
+        self.byte_index += op.size();
+        Some(Ok((offset, op)))
+    }
+}
+
+impl Iterator for QoiTokenizer<'_> {
+    type Item = Result<(usize, QoiOp), QoiError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_op()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consts::{DEFAULT_PIXEL, END_MARKER, ZERO_PIXEL},
+        error::QoiError,
+        header::QoiHeader,
+        pixel::Pixel,
+        utils::is_identical,
+    };
+    use super::{QoiOp, QoiTokenizer};
+    const INPUT: [u8; 31] = [113, 111, 105, 102,      // magic bytes (qoif)
+                             0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                             0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                             3,                       // channels (3 = RGB)
+                             0,                       // colorspace (0 = sRGB with linear alpha)
+                             254, 255, 255, 255,      // RGB chunk
+                             127,                     // Diff chunk (r+1, g+1, b+1)
+                             128, 55,                 // Luma chunk (r-37, g-32, b-33)
+                             38,                      // Index chunk
+                             195,                     // Run chunk (amount 4)
+                             0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    #[test]
+    const fn infallible_from_input() {
+        assert!(matches!(QoiOp::from_input(&INPUT, 14), QoiOp::Rgb(255, 255, 255)));
+        assert!(matches!(QoiOp::from_input(&INPUT, 18), QoiOp::Diff(1, 1, 1)));
+        assert!(matches!(QoiOp::from_input(&INPUT, 19), QoiOp::Luma(-32, -5, -1)));
+        assert!(matches!(QoiOp::from_input(&INPUT, 21), QoiOp::Index(38)));
+        assert!(matches!(QoiOp::from_input(&INPUT, 22), QoiOp::Run(4)));
+        assert!(matches!(QoiOp::from_input(&[255, 1, 2, 3, 4], 0), QoiOp::Rgba(1, 2, 3, 4)));
+        assert!(matches!(QoiOp::from_input(&[64, 0, 0, 0, 0], 0), QoiOp::Diff(-2, -2, -2)));
+    }
+    #[test]
+    const fn infallible_apply() {
+        let mut seen = [ZERO_PIXEL; 64];
+        let white = QoiOp::Rgb(255, 255, 255).apply(DEFAULT_PIXEL, &seen);
+        assert!(white.is_same(Pixel::new(255, 255, 255, 255)));
+        seen[white.calculate_hash_index()] = white;
+        let black = QoiOp::Diff(1, 1, 1).apply(white, &seen);
+        assert!(black.is_same(DEFAULT_PIXEL));
+        let luma = QoiOp::Luma(-32, -5, -1).apply(black, &seen);
+        assert!(luma.is_same(Pixel::new(219, 224, 223, 255)));
+        assert!(QoiOp::Index(38).apply(luma, &seen).is_same(white));
+        assert!(QoiOp::Run(4).apply(luma, &seen).is_same(luma));
+        assert!(QoiOp::Rgba(1, 2, 3, 4).apply(luma, &seen).is_same(Pixel::new(1, 2, 3, 4)));
+    }
+    #[test]
+    const fn good_to_bytes() {
+        assert!(matches!(QoiOp::Rgb(1, 2, 3).to_bytes(), Ok(([254, 1, 2, 3, 0], 4))));
+        assert!(matches!(QoiOp::Rgba(1, 2, 3, 4).to_bytes(), Ok(([255, 1, 2, 3, 4], 5))));
+        assert!(matches!(QoiOp::Index(63).to_bytes(), Ok(([63, 0, 0, 0, 0], 1))));
+        assert!(matches!(QoiOp::Diff(-2, 0, 1).to_bytes(), Ok(([75, 0, 0, 0, 0], 1))));   // b01001011
+        assert!(matches!(QoiOp::Luma(-32, -5, -1).to_bytes(), Ok(([128, 55, 0, 0, 0], 2))));
+        assert!(matches!(QoiOp::Luma(31, 7, -8).to_bytes(), Ok(([191, 240, 0, 0, 0], 2)))); // b10111111 b11110000
+        assert!(matches!(QoiOp::Run(1).to_bytes(), Ok(([192, 0, 0, 0, 0], 1))));
+        assert!(matches!(QoiOp::Run(62).to_bytes(), Ok(([253, 0, 0, 0, 0], 1))));
+    }
+    #[test]
+    const fn bad_to_bytes() {
+        assert!(matches!(QoiOp::Index(64).to_bytes(), Err(QoiError::InvalidOp(QoiOp::Index(64)))));
+        assert!(matches!(QoiOp::Diff(2, 0, 0).to_bytes(), Err(QoiError::InvalidOp(QoiOp::Diff(2, 0, 0)))));
+        assert!(matches!(QoiOp::Diff(0, -3, 0).to_bytes(), Err(QoiError::InvalidOp(_))));
+        assert!(matches!(QoiOp::Luma(32, 0, 0).to_bytes(), Err(QoiError::InvalidOp(_))));
+        assert!(matches!(QoiOp::Luma(0, 8, 0).to_bytes(), Err(QoiError::InvalidOp(_))));
+        assert!(matches!(QoiOp::Luma(0, 0, -9).to_bytes(), Err(QoiError::InvalidOp(_))));
+        assert!(matches!(QoiOp::Run(0).to_bytes(), Err(QoiError::InvalidOp(QoiOp::Run(0)))));
+        assert!(matches!(QoiOp::Run(63).to_bytes(), Err(QoiError::InvalidOp(QoiOp::Run(63)))));
+    }
+    #[test]
+    const fn good_write() {
+        let header = QoiHeader::new(2, 4, 3, 0);
+        assert!(header.is_ok());
+        if let Ok(header) = header {
+            let mut output = [0; 31];
+            let header = header.to_u8();
+            let mut output_index = 0;
+            while output_index != header.len() {
+                output[output_index] = header[output_index];
+                output_index += 1;
+            }
+            let ops = [QoiOp::Rgb(255, 255, 255), QoiOp::Diff(1, 1, 1), QoiOp::Luma(-32, -5, -1), QoiOp::Index(38), QoiOp::Run(4)];
+            let mut index = 0;
+            while index != ops.len() {
+                match ops[index].write(output, output_index) {
+                    Ok(both) => (output, output_index) = both,
+                    Err(_) => unreachable!(),
+                }
+                index += 1;
+            }
+            index = 0;
+            while index != END_MARKER.len() {
+                output[output_index] = END_MARKER[index];
+                output_index += 1;
+                index += 1;
+            }
+            assert!(is_identical(&output, &INPUT));
+        }
+    }
+    #[test]
+    const fn bad_write() {
+        assert!(matches!(QoiOp::Rgba(0, 0, 0, 0).write([0; 6], 2), Err(QoiError::NotEnoughSpace(5, 4))));
+        assert!(matches!(QoiOp::Run(1).write([0; 6], 7), Err(QoiError::NotEnoughSpace(1, 0))));
+        assert!(matches!(QoiOp::Run(63).write([0; 6], 0), Err(QoiError::InvalidOp(QoiOp::Run(63)))));
+    }
+    #[test]
+    const fn good_next_op() {
+        let tokenizer = QoiTokenizer::new(&INPUT);
+        assert!(tokenizer.is_ok());
+        if let Ok((mut tokenizer, header)) = tokenizer {
+            assert!(header.width() == 2);
+            assert!(matches!(tokenizer.next_op(), Some(Ok((14, QoiOp::Rgb(255, 255, 255))))));
+            assert!(matches!(tokenizer.next_op(), Some(Ok((18, QoiOp::Diff(1, 1, 1))))));
+            assert!(matches!(tokenizer.next_op(), Some(Ok((19, QoiOp::Luma(-32, -5, -1))))));
+            assert!(matches!(tokenizer.next_op(), Some(Ok((21, QoiOp::Index(38))))));
+            assert!(matches!(tokenizer.next_op(), Some(Ok((22, QoiOp::Run(4))))));
+            assert!(tokenizer.next_op().is_none());
+        }
+    }
+    #[test]
+    const fn bad_next_op() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     192,                     // Run chunk (amount 1)
+                     255, 255,                // RGBA chunk (incomplete, should have 3 more bytes)
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let tokenizer = QoiTokenizer::new(&input);
+        assert!(tokenizer.is_ok());
+        if let Ok((mut tokenizer, _)) = tokenizer {
+            assert!(matches!(tokenizer.next_op(), Some(Ok((14, QoiOp::Run(1))))));
+            assert!(matches!(tokenizer.next_op(), Some(Err(QoiError::ChunkIntoEndMarker(15)))));
+            assert!(tokenizer.next_op().is_none());
+        }
+    }
+}
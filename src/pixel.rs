@@ -24,6 +24,14 @@ impl Pixel {
         output[index] = self.alpha; index += 1;
         (output, index)
     }
+    // puts RGB pixel data in output buffer and increments output index. used only in decoder.
+    #[inline]
+    pub const fn to_rgb_output<const N: usize>(self, mut output: [u8; N], mut index: usize) -> ([u8; N], usize) {
+        output[index] = self.red; index += 1;
+        output[index] = self.green; index += 1;
+        output[index] = self.blue; index += 1;
+        (output, index)
+    }
     // puts RGB chunk tag and data in output buffer and increments output index. used only in encoder.
     #[inline]
     pub const fn rgb_to_output<const N: usize>(self, mut output: [u8; N], mut index: usize) -> ([u8; N], usize) {
@@ -106,6 +114,7 @@ mod tests {
         assert!(!a.is_same(c));
     }
     #[test]
+    #[allow(clippy::assertions_on_constants, clippy::identity_op)] // bitwise working shown for clarity
     const fn infallible_diff() {
         let mut new = Pixel::new(0, 0, 0, 255);         // red diff:   -1 stored as 1 (b01)
         let mut old = Pixel::new(1, 1, 1, 255);         // green diff: -1 stored as 1 (b01)
@@ -0,0 +1,540 @@
+use crate::{
+    decoder::{QoiDecoder, QoiDecoderProgress},
+    error::QoiError,
+    header::QoiHeader,
+};
+
+const BATCH: usize = 256;   // size in bytes of the decoded pixels held at once (64 pixels)
+const NODES: usize = 511;   // nodes of a tree with 256 leaves
+const LEAF: u8 = 4;         // axis value of a node that is not split
+
+/// Indicates whether the [`QoiQuantizer`] is finished.
+#[allow(clippy::large_enum_variant)]
+pub enum QoiQuantizerProgress<const N: usize, const W: usize> {
+    /// Returns [`QoiQuantizer`] for further processing and the full output buffer.
+    Unfinished(QoiQuantizer<W>, [u8; N]),
+    /// Returns the output buffer and the amount of bytes that should be considered as free space.
+    Finished([u8; N], usize),
+}
+
+/// A streaming colour quantizer that turns a QOI image with any amount of colours into a palette and `8` bit indices.
+///
+/// The palette is built with the median cut algorithm by decoding the image several times.
+/// Every round splits the boxes of colours holding the most pixels over the widest range at their median until the
+/// palette is full or every box holds a single colour, which takes `3` passes over the image.
+/// A final pass calculates the average colour of every box.
+/// No pass keeps more than a few decoded pixels so the memory used only depends on the size of the palette and, when
+/// dithering, on `W`.
+///
+/// `W` is the widest image that can be dithered with [`QoiQuantizer::with_dithering`] as `2` rows of errors are kept.
+/// It can be `0` when not dithering.
+///
+/// To generate a [`QoiQuantizer`] and retrieve the [`QoiHeader`] you must input the QOI image data as a slice of bytes.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy)]
+pub struct QoiQuantizer<const W: usize> {
+    decoder: QoiDecoder,
+    decoder_finished: bool,
+    pixels: [u8; BATCH],       // decoded RGBA pixels waiting to be quantized
+    pixels_end: usize,         // amount of bytes of decoded pixels in the batch
+    pixels_index: usize,       // index of the next decoded pixel in the batch
+    nodes: [Node; NODES],      // splits of the colour space with a palette entry for every leaf
+    palette: [[u8; 4]; 256],
+    colors: usize,             // amount of palette entries used
+    dither: bool,
+    width: u32,
+    position: u64,             // position of the next pixel to quantize
+    errors: [[[i16; 4]; W]; 2], // Floyd-Steinberg errors of the current and next row, multiplied by 16
+}
+
+impl<const W: usize> QoiQuantizer<W> {
+    /// Generates a [`QoiQuantizer`] and a [`QoiHeader`] from the input bytes of a QOI image, building a palette of at
+    /// most `colors` entries.
+    ///
+    /// Images with fewer distinct colours result in a smaller palette holding exactly those colours.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `colors` is `0` or more than `256` or under the same conditions as [`QoiDecoder::new`] and
+    /// [`QoiDecoder::process_chunks`] (except for the output buffer size).
+    pub const fn new(input: &[u8], colors: usize) -> Result<(Self, QoiHeader), QoiError> {
+        if colors == 0 || colors > 256 {return Err(QoiError::InvalidPaletteSize(colors));}
+        let (decoder, header) = match QoiDecoder::new(input) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let (nodes, palette, colors) = match build_palette(input, colors) {
+            Ok(built) => built,
+            Err(e) => return Err(e),
+        };
+        let quantizer = Self {
+            decoder,
+            decoder_finished: false,
+            pixels: [0; BATCH],
+            pixels_end: 0,
+            pixels_index: 0,
+            nodes,
+            palette,
+            colors,
+            dither: false,
+            width: header.width(),
+            position: 0,
+            errors: [[[0; 4]; W]; 2],
+        };
+        Ok((quantizer, header))
+    }
+    /// Enables Floyd-Steinberg dithering which spreads the difference between every pixel and its palette entry over
+    /// the neighbouring pixels.
+    ///
+    /// Dithering avoids banding in smooth gradients at the cost of searching the palette for every pixel.
+    /// Images wider than `W` cannot be dithered.
+    #[must_use]
+    pub const fn with_dithering(mut self) -> Self {
+        self.dither = true;
+        self
+    }
+    /// The palette entries as RGBA values in the order of the indices.
+    #[must_use]
+    pub const fn palette(&self) -> &[[u8; 4]] {
+        self.palette.split_at(self.colors).0
+    }
+    /// Processes the input bytes of the QOI image and fills the output buffer with the palette index of every pixel.
+    ///
+    /// The same input must be given on every call.
+    /// Every byte of output is the index of `1` pixel so any buffer size can be used and the buffer is guaranteed to
+    /// be full except on the final call.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if dithering an image wider than `W` or if the input is malformed in the same ways as for
+    /// [`QoiDecoder::process_chunks`].
+    pub const fn process<const N: usize>(mut self,
+                                         input: &[u8],
+                                         mut output: [u8; N]) -> Result<QoiQuantizerProgress<N, W>, QoiError> {
+        if self.dither && self.width as usize > W {return Err(QoiError::ImageTooWide(self.width, W));}
+        let mut output_index = 0;
+        loop {
+            if self.pixels_index == self.pixels_end {
+                if self.decoder_finished {return Ok(QoiQuantizerProgress::Finished(output, output.len() - output_index));}
+                match self.decoder.process_chunks(input, [0; BATCH]) {
+                    Ok(QoiDecoderProgress::Unfinished((decoder, pixels))) => {
+                        self.decoder = decoder;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH;
+                    },
+                    Ok(QoiDecoderProgress::Finished((pixels, empty, _))) => {
+                        self.decoder_finished = true;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH - empty;
+                    },
+                    Err(e) => return Err(e),
+                }
+                self.pixels_index = 0;
+                continue;
+            }
+            if output_index == output.len() {return Ok(QoiQuantizerProgress::Unfinished(self, output));}
+            let index = self.pixels_index;
+            let pixel = [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]];
+            let entry;
+            (self, entry) = if self.dither {self.dithered_entry(pixel)} else {(self, leaf(&self.nodes, pixel))};
+            output[output_index] = entry;
+            output_index += 1;
+            self.pixels_index += 4;
+            self.position += 1;
+        }
+    }
+    // chooses the nearest palette entry to the pixel with the errors of the previous pixels added
+    #[allow(clippy::cast_possible_truncation)] // position within a row always fits in a usize as it is below W
+    #[allow(clippy::cast_sign_loss)] // values are clamped to 0..=255 before casting
+    #[inline]
+    const fn dithered_entry(mut self, pixel: [u8; 4]) -> (Self, u8) {
+        let x = (self.position % self.width as u64) as usize;
+        let current = ((self.position / self.width as u64) % 2) as usize;
+        let next = 1 - current;
+        if x == 0 {self.errors[next] = [[0; 4]; W];}
+        let mut wanted = [0; 4];
+        let mut channel = 0;
+        while channel < 4 {
+            let value = pixel[channel] as i32 + (self.errors[current][x][channel] as i32 + 8).div_euclid(16);
+            wanted[channel] = if value < 0 {0} else if value > 255 {255} else {value as u8};
+            channel += 1;
+        }
+        let entry = nearest(self.palette(), wanted);
+        let chosen = self.palette[entry as usize];
+        let last = x + 1 == self.width as usize;
+        channel = 0;
+        while channel < 4 {
+            let error = wanted[channel] as i16 - chosen[channel] as i16;
+            if !last {self.errors[current][x + 1][channel] += error * 7;}
+            if x != 0 {self.errors[next][x - 1][channel] += error * 3;}
+            self.errors[next][x][channel] += error * 5;
+            if !last {self.errors[next][x + 1][channel] += error;}
+            channel += 1;
+        }
+        (self, entry)
+    }
+}
+
+// a split of the colour space or a palette entry when the axis is LEAF
+#[derive(Clone, Copy)]
+struct Node {
+    axis: u8,      // channel compared by the split
+    threshold: u8, // values up to the threshold belong to the left child
+    left: u16,     // index of the left child, the right child follows it
+    slot: u16,     // palette entry of a leaf
+}
+
+impl Node {
+    const fn leaf(slot: u16) -> Self {
+        Self {axis: LEAF, threshold: 0, left: 0, slot}
+    }
+}
+
+// what a pass over the image collects for every palette entry
+#[derive(Clone, Copy)]
+enum Scan {
+    Bounds, // amount of pixels, smallest and largest values and sums of values
+    Coarse, // histogram of the upper 4 bits of the values of the chosen axis
+    Fine,   // histogram of the lower 4 bits of the values of the chosen axis in the chosen upper 4 bits
+}
+
+#[derive(Clone, Copy)]
+struct Totals {
+    count: [u64; 256],
+    min: [[u8; 4]; 256],
+    max: [[u8; 4]; 256],
+    sums: [[u64; 4]; 256],
+    histogram: [[u64; 16]; 256],
+}
+
+// the splits of the colour space, the palette and the amount of palette entries used
+type Palette = ([Node; NODES], [[u8; 4]; 256], usize);
+
+// splits the colour space of the image at the median of the widest boxes until the palette is full
+#[allow(clippy::cast_possible_truncation)] // amount of leaves and nodes always fits in a u16
+const fn build_palette(input: &[u8], colors: usize) -> Result<Palette, QoiError> {
+    let mut nodes = [Node::leaf(0); NODES];
+    let mut leaf_nodes = [0; 256]; // node of every palette entry
+    let mut node_amount = 1;
+    let mut leaves = 1;
+    loop {
+        let bounds = match scan(input, &nodes, Scan::Bounds, &[LEAF; 256], &[0; 256]) {
+            Ok(totals) => totals,
+            Err(e) => return Err(e),
+        };
+        let mut widest = [0; 256];
+        let mut scores = [0; 256]; // pixels multiplied by the widest range, boxes of a single colour score 0
+        let mut slot = 0;
+        while slot < leaves {
+            let range;
+            (widest[slot], range) = widest_axis(bounds.min[slot], bounds.max[slot]);
+            scores[slot] = bounds.count[slot] * range as u64;
+            slot += 1;
+        }
+        let mut axes = [LEAF; 256];
+        let mut chosen = 0;
+        while leaves + chosen < colors {
+            let (mut best, mut best_score) = (256, 0);
+            slot = 0;
+            while slot < leaves {
+                if axes[slot] == LEAF && scores[slot] > best_score {(best, best_score) = (slot, scores[slot]);}
+                slot += 1;
+            }
+            if best == 256 {break;}
+            axes[best] = widest[best];
+            chosen += 1;
+        }
+        if chosen == 0 {
+            let mut palette = [[0; 4]; 256];
+            let mut slot = 0;
+            while slot < leaves {
+                let count = bounds.count[slot];
+                let mut channel = 0;
+                while channel < 4 {
+                    palette[slot][channel] = ((bounds.sums[slot][channel] + count / 2) / count) as u8;
+                    channel += 1;
+                }
+                slot += 1;
+            }
+            return Ok((nodes, palette, leaves));
+        }
+        let coarse = match scan(input, &nodes, Scan::Coarse, &axes, &[0; 256]) {
+            Ok(totals) => totals,
+            Err(e) => return Err(e),
+        };
+        let mut bins = [0; 256];
+        let mut below = [0; 256];
+        slot = 0;
+        while slot < leaves {
+            if axes[slot] != LEAF {
+                let target = bounds.count[slot].div_ceil(2);
+                while below[slot] + coarse.histogram[slot][bins[slot] as usize] < target {
+                    below[slot] += coarse.histogram[slot][bins[slot] as usize];
+                    bins[slot] += 1;
+                }
+            }
+            slot += 1;
+        }
+        let fine = match scan(input, &nodes, Scan::Fine, &axes, &bins) {
+            Ok(totals) => totals,
+            Err(e) => return Err(e),
+        };
+        let existing = leaves;
+        slot = 0;
+        while slot < existing {
+            let split_axis = axes[slot];
+            if split_axis != LEAF {
+                let target = bounds.count[slot].div_ceil(2);
+                let mut value = 0;
+                while below[slot] + fine.histogram[slot][value] < target {
+                    below[slot] += fine.histogram[slot][value];
+                    value += 1;
+                }
+                let mut threshold = bins[slot] * 16 + value as u8;
+                let max = bounds.max[slot][split_axis as usize];
+                if threshold >= max {threshold = max - 1;} // both sides keep pixels as the smallest value is below
+                let node = leaf_nodes[slot] as usize;
+                nodes[node] = Node {axis: split_axis, threshold, left: node_amount as u16, slot: 0};
+                nodes[node_amount] = Node::leaf(slot as u16);
+                nodes[node_amount + 1] = Node::leaf(leaves as u16);
+                leaf_nodes[slot] = node_amount as u16;
+                leaf_nodes[leaves] = node_amount as u16 + 1;
+                node_amount += 2;
+                leaves += 1;
+            }
+            slot += 1;
+        }
+    }
+}
+
+// decodes the whole image once collecting the chosen totals for every palette entry
+#[allow(clippy::large_stack_arrays)] // fixed amount of working memory no matter the size of the image
+const fn scan(input: &[u8], nodes: &[Node; NODES], scan: Scan, axes: &[u8; 256], bins: &[u8; 256]) -> Result<Totals, QoiError> {
+    let mut totals = Totals {
+        count: [0; 256],
+        min: [[255; 4]; 256],
+        max: [[0; 4]; 256],
+        sums: [[0; 4]; 256],
+        histogram: [[0; 16]; 256],
+    };
+    let mut decoder = match QoiDecoder::new(input) {
+        Ok((decoder, _)) => decoder,
+        Err(e) => return Err(e),
+    };
+    loop {
+        let (pixels, end, finished) = match decoder.process_chunks(input, [0; BATCH]) {
+            Ok(QoiDecoderProgress::Unfinished((unfinished, pixels))) => {decoder = unfinished; (pixels, BATCH, false)},
+            Ok(QoiDecoderProgress::Finished((pixels, empty, _))) => (pixels, BATCH - empty, true),
+            Err(e) => return Err(e),
+        };
+        let mut index = 0;
+        while index < end {
+            let pixel = [pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3]];
+            let slot = leaf(nodes, pixel) as usize;
+            let chosen = axes[slot] as usize;
+            match scan {
+                Scan::Bounds => {
+                    totals.count[slot] += 1;
+                    let mut channel = 0;
+                    while channel < 4 {
+                        let value = pixel[channel];
+                        if value < totals.min[slot][channel] {totals.min[slot][channel] = value;}
+                        if value > totals.max[slot][channel] {totals.max[slot][channel] = value;}
+                        totals.sums[slot][channel] += value as u64;
+                        channel += 1;
+                    }
+                },
+                Scan::Coarse => if chosen != LEAF as usize {totals.histogram[slot][(pixel[chosen] >> 4) as usize] += 1;},
+                Scan::Fine => if chosen != LEAF as usize && pixel[chosen] >> 4 == bins[slot] {
+                    totals.histogram[slot][(pixel[chosen] & 0x0f) as usize] += 1;
+                },
+            }
+            index += 4;
+        }
+        if finished {return Ok(totals);}
+    }
+}
+
+// the palette entry of the box holding the pixel
+#[allow(clippy::cast_possible_truncation)] // palettes have at most 256 entries so cannot truncate when casting to u8
+#[inline]
+const fn leaf(nodes: &[Node; NODES], pixel: [u8; 4]) -> u8 {
+    let mut node = 0;
+    while nodes[node].axis != LEAF {
+        let split = nodes[node];
+        node = split.left as usize + if pixel[split.axis as usize] > split.threshold {1} else {0};
+    }
+    nodes[node].slot as u8
+}
+
+// the channel with the largest difference between its smallest and largest value and that difference
+#[allow(clippy::cast_possible_truncation)] // channel is always 0..=3 so cannot truncate when casting to u8
+#[inline]
+const fn widest_axis(min: [u8; 4], max: [u8; 4]) -> (u8, u8) {
+    let (mut axis, mut range, mut channel) = (0, 0, 0);
+    while channel < 4 {
+        let difference = max[channel].saturating_sub(min[channel]);
+        if difference > range {(axis, range) = (channel as u8, difference);}
+        channel += 1;
+    }
+    (axis, range)
+}
+
+// the palette entry with the smallest squared distance to the colour, the first one when several are equal
+#[allow(clippy::cast_possible_truncation)] // palettes have at most 256 entries so cannot truncate when casting to u8
+#[inline]
+const fn nearest(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let (mut best, mut best_distance, mut entry) = (0, u32::MAX, 0);
+    while entry < palette.len() {
+        let mut distance = 0;
+        let mut channel = 0;
+        while channel < 4 {
+            let difference = palette[entry][channel].abs_diff(color[channel]) as u32;
+            distance += difference * difference;
+            channel += 1;
+        }
+        if distance < best_distance {(best, best_distance) = (entry, distance);}
+        entry += 1;
+    }
+    best as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{embed::{encode_to_array, encoded_size}, error::QoiError, utils::is_identical};
+    use super::{QoiQuantizer, QoiQuantizerProgress};
+    const GRADIENT_PIXELS: [u8; 32 * 32 * 3] = gradient(); // 1024 distinct colours
+    const GRADIENT_SIZE: usize = match encoded_size(&GRADIENT_PIXELS, 32, 32, 3, 0) {Ok(size) => size, Err(_) => 0};
+    const GRADIENT: [u8; GRADIENT_SIZE] = match encode_to_array(&GRADIENT_PIXELS, 32, 32, 3, 0) {
+        Ok(image) => image,
+        Err(_) => [0; GRADIENT_SIZE],
+    };
+    #[allow(clippy::cast_possible_truncation)] // values always fit in a u8
+    const fn gradient() -> [u8; 32 * 32 * 3] {
+        let mut pixels = [0; 32 * 32 * 3];
+        let mut index = 0;
+        while index < 32 * 32 {
+            let (x, y) = (index % 32, index / 32);
+            pixels[index * 3] = (x * 8) as u8;
+            pixels[index * 3 + 1] = (y * 8) as u8;
+            pixels[index * 3 + 2] = ((x + y) * 4) as u8;
+            index += 1;
+        }
+        pixels
+    }
+    const INPUT: [u8; 32] = [113, 111, 105, 102,      // magic bytes (qoif)
+                             0, 0, 0, 4,              // width (4xu8 into 1xu32 big endian: 4)
+                             0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                             3,                       // channels (3 = RGB)
+                             0,                       // colorspace (0 = sRGB with linear alpha)
+                             192,                     // Run chunk (amount 1, black)
+                             254, 255, 255, 255,      // RGB chunk (white)
+                             254, 255, 0, 0,          // RGB chunk (red)
+                             192,                     // Run chunk (amount 1, red)
+                             0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    #[test]
+    const fn good_new() {
+        if let Ok((quantizer, header)) = QoiQuantizer::<0>::new(&INPUT, 2) {
+            assert!(header.width() == 4);
+            let palette = quantizer.palette();
+            assert!(palette.len() == 2);
+            assert!(is_identical(&palette[0], &[0, 0, 0, 255]));
+            assert!(is_identical(&palette[1], &[255, 85, 85, 255])); // average of white and 2 red pixels
+        } else {
+            unreachable!();
+        }
+        if let Ok((quantizer, _)) = QoiQuantizer::<0>::new(&INPUT, 256) {
+            let palette = quantizer.palette();
+            assert!(palette.len() == 3); // only 3 distinct colours
+            assert!(is_identical(&palette[1], &[255, 0, 0, 255]) && is_identical(&palette[2], &[255, 255, 255, 255]));
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn bad_new() {
+        assert!(matches!(QoiQuantizer::<0>::new(&INPUT, 0), Err(QoiError::InvalidPaletteSize(0))));
+        assert!(matches!(QoiQuantizer::<0>::new(&INPUT, 257), Err(QoiError::InvalidPaletteSize(257))));
+        assert!(matches!(QoiQuantizer::<0>::new(INPUT.split_at(20).0, 2), Err(QoiError::InputTooSmall(20))));
+    }
+    #[test]
+    const fn good_process() {
+        if let Ok((quantizer, _)) = QoiQuantizer::<0>::new(&INPUT, 3) {
+            if let Ok(QoiQuantizerProgress::Unfinished(quantizer, buffer)) = quantizer.process(&INPUT, [9; 3]) {
+                assert!(is_identical(&buffer, &[0, 2, 1]));
+                if let Ok(QoiQuantizerProgress::Finished(buffer, empty)) = quantizer.process(&INPUT, [9; 3]) {
+                    assert!(empty == 2 && buffer[0] == 1);
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        if let Ok((quantizer, _)) = QoiQuantizer::<0>::new(&INPUT, 2) {
+            assert!(matches!(quantizer.with_dithering().process(&INPUT, [0; 4]), Err(QoiError::ImageTooWide(4, 0))));
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn good_process_gradient() {
+        let mut dither = 0;
+        while dither < 2 {
+            if let Ok((mut quantizer, _)) = QoiQuantizer::<32>::new(&GRADIENT, 16) {
+                assert!(quantizer.palette().len() == 16);
+                if dither == 1 {quantizer = quantizer.with_dithering();}
+                let mut pixels = 0;
+                loop {
+                    match quantizer.process(&GRADIENT, [0; 100]) {
+                        Ok(QoiQuantizerProgress::Unfinished(unfinished, buffer)) => {
+                            let mut index = 0;
+                            while index < buffer.len() {assert!(buffer[index] < 16); index += 1;}
+                            quantizer = unfinished;
+                            pixels += buffer.len();
+                        },
+                        Ok(QoiQuantizerProgress::Finished(buffer, empty)) => {
+                            let mut index = 0;
+                            while index < buffer.len() - empty {assert!(buffer[index] < 16); index += 1;}
+                            pixels += buffer.len() - empty;
+                            break;
+                        },
+                        Err(_) => unreachable!(),
+                    }
+                }
+                assert!(pixels == 32 * 32);
+            } else {
+                unreachable!();
+            }
+            dither += 1;
+        }
+        if let Ok((quantizer, _)) = QoiQuantizer::<0>::new(&GRADIENT, 256) {
+            assert!(quantizer.palette().len() == 256);
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn good_process_dithered() {
+        let mut input = INPUT;
+        input[19] = 254; input[20] = 200; input[21] = 200; input[22] = 200; // RGB chunk (grey 200 instead of red)
+        if let Ok((quantizer, _)) = QoiQuantizer::<4>::new(&input, 2) {
+            assert!(is_identical(&quantizer.palette()[0], &[133, 133, 133, 255])); // average of black and 2 grey
+            if let Ok(QoiQuantizerProgress::Finished(buffer, _)) = quantizer.process(&input, [9; 4]) {
+                assert!(is_identical(&buffer, &[0, 1, 0, 0]));
+            } else {
+                unreachable!();
+            }
+            if let Ok(QoiQuantizerProgress::Finished(buffer, empty)) = quantizer.with_dithering().process(&input, [9; 4]) {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, &[0, 1, 0, 1])); // errors of the previous pixels brighten the last grey
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    consts::{DEFAULT_PIXEL, ZERO_PIXEL},
+    decoder::{QoiDecoder, QoiDecoderProgress},
+    encoder::QoiEncoderInternal,
+    error::QoiError,
+    header::QoiHeader,
+    op::QoiOp,
+    pixel::Pixel,
+};
+
+const BATCH: usize = 64; // size in bytes of the decoded pixels held at once (16 pixels)
+
+/// Indicates whether the [`QoiReencoder`] is finished.
+#[allow(clippy::large_enum_variant)]
+pub enum QoiReencoderProgress<const N: usize> {
+    /// Returns [`QoiReencoder`] for further processing, the output buffer and the empty space left in the output buffer.
+    ///
+    /// The encoded QOI chunks can vary in size and are never split between output buffers.
+    /// Due to the different size chunks the buffer may not always be returned full.
+    Unfinished(QoiReencoder, [u8; N], usize),
+    /// Returns the output buffer and the amount of bytes that should be considered as free space.
+    Finished([u8; N], usize),
+}
+
+/// A streaming re-encoder that turns a QOI image from any encoder into the chunks the [`QoiEncoder`](crate::QoiEncoder)
+/// would produce for the same pixels.
+///
+/// The input is decoded and encoded again a few pixels at a time so only a small fixed amount of memory is used no
+/// matter the size of the image.
+/// Every generated chunk is decoded again and compared with the input pixels to guarantee nothing has changed.
+///
+/// To generate a [`QoiReencoder`] and retrieve the [`QoiHeader`] you must input the QOI image data as a slice of bytes.\
+/// The header is unchanged so its bytes from [`QoiHeader::to_u8`] come first in the output followed by the chunks and
+/// the [`END_MARKER`](crate::END_MARKER).
+/// [`QoiLinter`](crate::QoiLinter) can be used beforehand to find out whether re-encoding would save any bytes.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy)]
+pub struct QoiReencoder {
+    decoder: QoiDecoder,
+    decoder_finished: bool,
+    pixels: [u8; BATCH],       // decoded RGBA pixels waiting to be encoded
+    pixels_end: usize,         // amount of bytes of decoded pixels in the batch
+    pixels_index: usize,       // index of the next decoded pixel in the batch
+    encoder: QoiEncoderInternal,
+    encoder_finished: bool,
+    pending: [Option<QoiOp>; 2], // generated chunks waiting for space in the output buffer
+    checked_pixels: u64,       // amount of pixels reproduced by the generated chunks
+    check_seen_pixels: [Pixel; 64],
+    check_previous_pixel: Pixel,
+    last_pixel: Pixel,         // last pixel given to the encoder
+}
+
+impl QoiReencoder {
+    /// Generates a [`QoiReencoder`] and a [`QoiHeader`] from the input bytes of a QOI image.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same conditions as [`QoiDecoder::new`].
+    pub const fn new(input: &[u8]) -> Result<(Self, QoiHeader), QoiError> {
+        let (decoder, header) = match QoiDecoder::new(input) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let reencoder = Self {
+            decoder,
+            decoder_finished: false,
+            pixels: [0; BATCH],
+            pixels_end: 0,
+            pixels_index: 0,
+            encoder: QoiEncoderInternal::new(header.pixel_amount(), header.channels() == 4, header.colorspace(),
+                                             header.width()),
+            encoder_finished: false,
+            pending: [None, None],
+            checked_pixels: 0,
+            check_seen_pixels: [ZERO_PIXEL; 64],
+            check_previous_pixel: DEFAULT_PIXEL,
+            last_pixel: DEFAULT_PIXEL,
+        };
+        Ok((reencoder, header))
+    }
+    /// Processes the input bytes of the QOI image and fills the output buffer with bytes representing QOI data chunks.
+    ///
+    /// The same input must be given on every call.
+    /// The minimum size buffer required is `5` bytes.
+    /// This is equivalent to the largest returnable QOI data chunk.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if output buffer is less than `5` bytes, if the generated chunks do not reproduce the input
+    /// pixels or if the input is malformed in the same ways as for [`QoiDecoder::process_chunks`].
+    pub const fn process<const N: usize>(mut self,
+                                         input: &[u8],
+                                         mut output: [u8; N]) -> Result<QoiReencoderProgress<N>, QoiError> {
+        if output.len() < 5 {return Err(QoiError::BufferTooSmall(output.len()));}
+        let mut output_index = 0;
+        loop {
+            while let Some(op) = self.pending[0] {
+                if output.len() - output_index < op.size() {
+                    return Ok(QoiReencoderProgress::Unfinished(self, output, output.len() - output_index));
+                }
+                match op.write(output, output_index) {
+                    Ok(both) => (output, output_index) = both,
+                    Err(e) => return Err(e),
+                }
+                self.pending = [self.pending[1], None];
+            }
+            if self.pixels_index == self.pixels_end {
+                if self.decoder_finished {
+                    if self.encoder_finished {return Ok(QoiReencoderProgress::Finished(output, output.len() - output_index));}
+                    let run;
+                    (self.encoder, run) = self.encoder.finish_pixels();
+                    self.encoder_finished = true;
+                    match self.check(run, self.last_pixel) {
+                        Ok(checked) => self = checked,
+                        Err(e) => return Err(e),
+                    }
+                    self.pending = [run, None];
+                    continue;
+                }
+                match self.decoder.process_chunks(input, [0; BATCH]) {
+                    Ok(QoiDecoderProgress::Unfinished((decoder, pixels))) => {
+                        self.decoder = decoder;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH;
+                    },
+                    Ok(QoiDecoderProgress::Finished((pixels, empty, _))) => {
+                        self.decoder_finished = true;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH - empty;
+                    },
+                    Err(e) => return Err(e),
+                }
+                self.pixels_index = 0;
+                continue;
+            }
+            let index = self.pixels_index;
+            let pixel = Pixel::new(self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]);
+            self.pixels_index += 4;
+            let (run, op);
+            (self.encoder, run, op) = self.encoder.push_pixel(pixel);
+            match self.check(run, self.last_pixel) {
+                Ok(checked) => self = checked,
+                Err(e) => return Err(e),
+            }
+            match self.check(op, pixel) {
+                Ok(checked) => self = checked,
+                Err(e) => return Err(e),
+            }
+            self.last_pixel = pixel;
+            self.pending = if run.is_some() {[run, op]} else {[op, None]};
+        }
+    }
+    // decodes the generated chunk and compares it with the pixel it was generated from
+    const fn check(mut self, op: Option<QoiOp>, pixel: Pixel) -> Result<Self, QoiError> {
+        let Some(op) = op else {return Ok(self)};
+        let decoded = op.apply(self.check_previous_pixel, &self.check_seen_pixels);
+        if !decoded.is_same(pixel) {return Err(QoiError::ReencodeMismatch(self.checked_pixels));}
+        self.check_seen_pixels[decoded.calculate_hash_index()] = decoded;
+        self.check_previous_pixel = decoded;
+        self.checked_pixels += op.pixel_amount() as u64;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::QoiError, utils::is_identical};
+    use super::{QoiReencoder, QoiReencoderProgress};
+    const INPUT: [u8; 42] = [113, 111, 105, 102,      // magic bytes (qoif)
+                             0, 0, 0, 9,              // width (4xu8 into 1xu32 big endian: 9)
+                             0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                             4,                       // channels (4 = RGBA)
+                             0,                       // colorspace (0 = sRGB with linear alpha)
+                             255, 200, 10, 10, 255,   // RGBA chunk, alpha unchanged (RGB would do)
+                             255, 201, 11, 11, 255,   // RGBA chunk, diff would do
+                             192,                     // Run chunk (amount 1)
+                             193,                     // Run chunk (amount 2), split from previous run
+                             254, 200, 10, 10,        // RGB chunk, index would do
+                             5,                       // Index chunk, same as previous pixel
+                             160, 136,                // Luma chunk, no change from previous pixel
+                             65,                      // Diff chunk (r-2, g-2, b-1)
+                             0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    const EXPECTED: [u8; 9] = [254, 200, 10, 10, // RGB chunk
+                               127,              // Diff chunk (r+1, g+1, b+1)
+                               194,              // Run chunk (amount 3)
+                               5,                // Index chunk
+                               193,              // Run chunk (amount 2)
+                               65];              // Diff chunk (r-2, g-2, b-1)
+    #[test]
+    const fn good_process_finished() {
+        let both = QoiReencoder::new(&INPUT);
+        assert!(both.is_ok());
+        if let Ok((reencoder, header)) = both {
+            assert!(header.width() == 9);
+            let progress = reencoder.process(&INPUT, [0; 16]);
+            assert!(progress.is_ok());
+            if let Ok(QoiReencoderProgress::Finished(buffer, empty)) = progress {
+                assert!(empty == 7);
+                assert!(is_identical(buffer.split_at(9).0, &EXPECTED));
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn good_process_unfinished() {
+        let both = QoiReencoder::new(&INPUT);
+        assert!(both.is_ok());
+        if let Ok((reencoder, _)) = both {
+            let progress = reencoder.process(&INPUT, [0; 6]);
+            assert!(progress.is_ok());
+            if let Ok(QoiReencoderProgress::Unfinished(reencoder, buffer, empty)) = progress {
+                assert!(empty == 0);
+                assert!(is_identical(&buffer, EXPECTED.split_at(6).0));
+                let progress = reencoder.process(&INPUT, [0; 6]);
+                assert!(progress.is_ok());
+                if let Ok(QoiReencoderProgress::Finished(buffer, empty)) = progress {
+                    assert!(empty == 3);
+                    assert!(is_identical(buffer.split_at(3).0, EXPECTED.split_at(6).1));
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        }
+    }
+    #[test]
+    const fn bad_process() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 4,              // height (4xu8 into 1xu32 big endian: 4)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     255, 255, 255, 255, 255, // RGBA chunk
+                                              // missing chunks: header stated 8 pixels only found 1
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        let both = QoiReencoder::new(&input);
+        assert!(both.is_ok());
+        if let Ok((reencoder, _)) = both {
+            assert!(matches!(reencoder.process(&input, [0; 16]), Err(QoiError::IncorrectPixelAmount(8, 1))));
+        }
+        let both = QoiReencoder::new(&input);
+        if let Ok((reencoder, _)) = both {
+            assert!(matches!(reencoder.process(&input, [0; 4]), Err(QoiError::BufferTooSmall(4))));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::{
+    color::{linear_to_srgb, u16_to_u8, SRGB_TO_LINEAR},
+    decoder::{QoiDecoder, QoiDecoderProgress},
+    error::QoiError,
+    header::{QoiHeader, QoiHeaderInternal},
+};
+
+const BATCH: usize = 256; // size in bytes of the decoded pixels held at once (64 pixels)
+
+/// Indicates whether the [`QoiScaler`] is finished.
+#[allow(clippy::large_enum_variant)]
+pub enum QoiScalerProgress<const N: usize, const W: usize> {
+    /// Returns [`QoiScaler`] for further processing and the full output buffer.
+    Unfinished(QoiScaler<W>, [u8; N]),
+    /// Returns the output buffer and the amount of bytes that should be considered as free space.
+    Finished([u8; N], usize),
+}
+
+/// A streaming decoder that shrinks or enlarges a QOI image by an integer factor while decoding.
+///
+/// Shrinking averages every box of `factor` by `factor` pixels into `1` pixel which is useful for thumbnails.
+/// Colour values are weighted by alpha so the colour of transparent pixels does not bleed into their neighbours and,
+/// when the colorspace value is `0` (sRGB with linear alpha), averaged in linear light.
+/// Boxes at the right and bottom edges of images not divisible by the factor hold fewer pixels.
+///
+/// Enlarging repeats every pixel `factor` times in both directions (nearest neighbour) which keeps pixel art sharp.
+///
+/// Only `1` row is kept no matter the height of the image.
+/// `W` is the widest row that can be held, the width of the output when shrinking and of the input when enlarging.
+///
+/// To generate a [`QoiScaler`] and retrieve the [`QoiHeader`] of the output you must input the QOI image data as a
+/// slice of bytes.
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)] // the flags are independent of each other
+#[derive(Clone, Copy)]
+pub struct QoiScaler<const W: usize> {
+    decoder: QoiDecoder,
+    decoder_finished: bool,
+    pixels: [u8; BATCH],    // decoded RGBA pixels waiting to be scaled
+    pixels_end: usize,      // amount of bytes of decoded pixels in the batch
+    pixels_index: usize,    // index of the next decoded pixel in the batch
+    factor: u32,
+    enlarge: bool,
+    linear_light: bool,     // whether colour values are averaged in linear light
+    width: u32,             // width of the input image
+    height: u32,            // height of the input image
+    x: u32,                 // position of the next decoded pixel within its row
+    y: u32,                 // row of the next decoded pixel
+    row: [[u64; 4]; W],     // alpha weighted sums of every box when shrinking, input pixels when enlarging
+    rows: u32,              // input rows added to the boxes since they were last written
+    writing: bool,          // whether the row is being written to the output
+    written: u32,           // amount of pixels of the row already written
+    repeats: u32,           // times the input row has been written when enlarging
+}
+
+impl<const W: usize> QoiScaler<W> {
+    /// Generates a [`QoiScaler`] shrinking the image by `factor` and a [`QoiHeader`] describing the output.
+    ///
+    /// The output width and height are the input width and height divided by `factor` and rounded up.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `factor` is `0`, the output is wider than `W` or under the same conditions as
+    /// [`QoiDecoder::new`].
+    pub const fn new_shrunk(input: &[u8], factor: u32) -> Result<(Self, QoiHeader), QoiError> {
+        if factor == 0 {return Err(QoiError::InvalidScaleFactor(factor));}
+        let (scaler, header) = match Self::new(input, factor, false) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let (width, height) = (header.width().div_ceil(factor), header.height().div_ceil(factor));
+        Ok((scaler, QoiHeaderInternal::new(width, height, header.channels(), header.colorspace()).public()))
+    }
+    /// Generates a [`QoiScaler`] enlarging the image by `factor` and a [`QoiHeader`] describing the output.
+    ///
+    /// The output width and height are the input width and height multiplied by `factor`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `factor` is `0`, the output width or height would not fit in a header, the input is wider
+    /// than `W` or under the same conditions as [`QoiDecoder::new`].
+    pub const fn new_enlarged(input: &[u8], factor: u32) -> Result<(Self, QoiHeader), QoiError> {
+        if factor == 0 {return Err(QoiError::InvalidScaleFactor(factor));}
+        let (scaler, header) = match Self::new(input, factor, true) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        match (header.width().checked_mul(factor), header.height().checked_mul(factor)) {
+            (Some(width), Some(height)) => {
+                Ok((scaler, QoiHeaderInternal::new(width, height, header.channels(), header.colorspace()).public()))
+            },
+            _ => Err(QoiError::InvalidScaleFactor(factor)),
+        }
+    }
+    // generates the scaler and returns the header of the input
+    const fn new(input: &[u8], factor: u32, enlarge: bool) -> Result<(Self, QoiHeader), QoiError> {
+        let (decoder, header) = match QoiDecoder::new(input) {
+            Ok(both) => both,
+            Err(e) => return Err(e),
+        };
+        let row_width = if enlarge {header.width()} else {header.width().div_ceil(factor)};
+        if row_width as usize > W {return Err(QoiError::ImageTooWide(row_width, W));}
+        let scaler = Self {
+            decoder,
+            decoder_finished: false,
+            pixels: [0; BATCH],
+            pixels_end: 0,
+            pixels_index: 0,
+            factor,
+            enlarge,
+            linear_light: header.colorspace() == 0,
+            width: header.width(),
+            height: header.height(),
+            x: 0,
+            y: 0,
+            row: [[0; 4]; W],
+            rows: 0,
+            writing: false,
+            written: 0,
+            repeats: 0,
+        };
+        Ok((scaler, header))
+    }
+    /// Processes the input bytes of the QOI image and fills the output buffer with bytes representing RGBA pixel values
+    /// of the scaled image.
+    ///
+    /// The same input must be given on every call.
+    /// The output buffer is guaranteed to be full except on the final call.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if output buffer is not divisible by `4` or if the input is malformed in the same ways as for
+    /// [`QoiDecoder::process_chunks`].
+    pub const fn process<const N: usize>(mut self,
+                                         input: &[u8],
+                                         mut output: [u8; N]) -> Result<QoiScalerProgress<N, W>, QoiError> {
+        if output.len() % 4 != 0 {return Err(QoiError::IncorrectBufferSize(output.len()));}
+        let mut output_index = 0;
+        loop {
+            if self.writing {
+                let row_width = if self.enlarge {self.width * self.factor} else {self.width.div_ceil(self.factor)};
+                while self.written < row_width {
+                    if output_index == output.len() {return Ok(QoiScalerProgress::Unfinished(self, output));}
+                    let pixel = if self.enlarge {self.repeated(self.written)} else {self.average(self.written)};
+                    output[output_index] = pixel[0];
+                    output[output_index + 1] = pixel[1];
+                    output[output_index + 2] = pixel[2];
+                    output[output_index + 3] = pixel[3];
+                    output_index += 4;
+                    self.written += 1;
+                }
+                self.written = 0;
+                if self.enlarge {
+                    self.repeats += 1;
+                    if self.repeats < self.factor {continue;}
+                    self.repeats = 0;
+                } else {
+                    self.row = [[0; 4]; W];
+                    self.rows = 0;
+                }
+                self.writing = false;
+                continue;
+            }
+            if self.y == self.height {return Ok(QoiScalerProgress::Finished(output, output.len() - output_index));}
+            if self.pixels_index == self.pixels_end {
+                if self.decoder_finished {return Ok(QoiScalerProgress::Finished(output, output.len() - output_index));}
+                match self.decoder.process_chunks(input, [0; BATCH]) {
+                    Ok(QoiDecoderProgress::Unfinished((decoder, pixels))) => {
+                        self.decoder = decoder;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH;
+                    },
+                    Ok(QoiDecoderProgress::Finished((pixels, empty, _))) => {
+                        self.decoder_finished = true;
+                        self.pixels = pixels;
+                        self.pixels_end = BATCH - empty;
+                    },
+                    Err(e) => return Err(e),
+                }
+                self.pixels_index = 0;
+                continue;
+            }
+            let index = self.pixels_index;
+            let pixel = [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]];
+            self.pixels_index += 4;
+            self = self.add(pixel);
+            self.x += 1;
+            if self.x == self.width {
+                self.x = 0;
+                self.y += 1;
+                self.rows += 1;
+                self.writing = self.enlarge || self.rows == self.factor || self.y == self.height;
+            }
+        }
+    }
+    // adds the decoded pixel to its box when shrinking or keeps it when enlarging
+    const fn add(mut self, pixel: [u8; 4]) -> Self {
+        if self.enlarge {
+            self.row[self.x as usize] = [pixel[0] as u64, pixel[1] as u64, pixel[2] as u64, pixel[3] as u64];
+            return self;
+        }
+        let column = (self.x / self.factor) as usize;
+        let alpha = pixel[3] as u64;
+        let mut channel = 0;
+        while channel < 3 {
+            let value = if self.linear_light {SRGB_TO_LINEAR[pixel[channel] as usize]} else {pixel[channel] as u16 * 257};
+            self.row[column][channel] += value as u64 * alpha;
+            channel += 1;
+        }
+        self.row[column][3] += alpha;
+        self
+    }
+    // the input pixel repeated at the column of the output when enlarging
+    #[allow(clippy::cast_possible_truncation)] // values were stored from bytes so cannot truncate
+    const fn repeated(&self, column: u32) -> [u8; 4] {
+        let [red, green, blue, alpha] = self.row[(column / self.factor) as usize];
+        [red as u8, green as u8, blue as u8, alpha as u8]
+    }
+    // the average of the box written to the output at the column. fully transparent boxes become 0, 0, 0, 0
+    #[allow(clippy::cast_possible_truncation)] // averages of 16 bit values and alpha values always fit
+    const fn average(&self, column: u32) -> [u8; 4] {
+        let columns = if (column + 1) * self.factor > self.width {self.width - column * self.factor} else {self.factor};
+        let pixel_amount = columns as u64 * self.rows as u64;
+        let [red, green, blue, alpha] = self.row[column as usize];
+        if alpha == 0 {return [0; 4];}
+        let color = [red, green, blue];
+        let mut result = [0; 4];
+        let mut channel = 0;
+        while channel < 3 {
+            let value = ((color[channel] + alpha / 2) / alpha) as u16;
+            result[channel] = if self.linear_light {linear_to_srgb(value)} else {u16_to_u8(value)};
+            channel += 1;
+        }
+        result[3] = ((alpha + pixel_amount / 2) / pixel_amount) as u8;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::QoiError, utils::is_identical};
+    use super::{QoiScaler, QoiScalerProgress};
+    const INPUT: [u8; 58] = [113, 111, 105, 102,      // magic bytes (qoif)
+                             0, 0, 0, 3,              // width (4xu8 into 1xu32 big endian: 3)
+                             0, 0, 0, 3,              // height (4xu8 into 1xu32 big endian: 3)
+                             3,                       // channels (3 = RGB)
+                             1,                       // colorspace (1 = all channels linear)
+                             254, 0, 0, 0,            // RGB chunk
+                             254, 10, 10, 10,         // RGB chunk
+                             254, 100, 100, 100,      // RGB chunk
+                             254, 20, 20, 20,         // RGB chunk
+                             254, 30, 30, 30,         // RGB chunk
+                             254, 200, 200, 200,      // RGB chunk
+                             254, 50, 50, 50,         // RGB chunk
+                             254, 60, 60, 60,         // RGB chunk
+                             254, 7, 7, 7,            // RGB chunk
+                             0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+    #[test]
+    const fn good_process_shrunk() {
+        if let Ok((scaler, header)) = QoiScaler::<2>::new_shrunk(&INPUT, 2) {
+            assert!(header.width() == 2 && header.height() == 2);
+            if let Ok(QoiScalerProgress::Unfinished(scaler, buffer)) = scaler.process(&INPUT, [0; 8]) {
+                assert!(is_identical(&buffer, &[15, 15, 15, 255,     // average of 4 pixels
+                                                150, 150, 150, 255])); // average of 2 pixels at the right edge
+                if let Ok(QoiScalerProgress::Finished(buffer, empty)) = scaler.process(&INPUT, [0; 8]) {
+                    assert!(empty == 0);
+                    assert!(is_identical(&buffer, &[55, 55, 55, 255, // average of 2 pixels at the bottom edge
+                                                    7, 7, 7, 255])); // single pixel in the corner
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+        let srgb = [113, 111, 105, 102,      // magic bytes (qoif)
+                    0, 0, 0, 4,              // width (4xu8 into 1xu32 big endian: 4)
+                    0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                    4,                       // channels (4 = RGBA)
+                    0,                       // colorspace (0 = sRGB with linear alpha)
+                    254, 255, 255, 255,      // RGB chunk
+                    254, 0, 0, 0,            // RGB chunk
+                    254, 255, 0, 0,          // RGB chunk
+                    255, 0, 0, 255, 0,       // RGBA chunk, fully transparent
+                    0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((scaler, _)) = QoiScaler::<2>::new_shrunk(&srgb, 2) {
+            if let Ok(QoiScalerProgress::Finished(buffer, empty)) = scaler.process(&srgb, [0; 12]) {
+                assert!(empty == 4);
+                assert!(is_identical(buffer.split_at(8).0, &[188, 188, 188, 255, // mixed in linear light
+                                                             255, 0, 0, 128]));  // transparent colour ignored
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn good_process_enlarged() {
+        let input = [113, 111, 105, 102,      // magic bytes (qoif)
+                     0, 0, 0, 2,              // width (4xu8 into 1xu32 big endian: 2)
+                     0, 0, 0, 1,              // height (4xu8 into 1xu32 big endian: 1)
+                     4,                       // channels (4 = RGBA)
+                     0,                       // colorspace (0 = sRGB with linear alpha)
+                     254, 1, 2, 3,            // RGB chunk
+                     255, 4, 5, 6, 7,         // RGBA chunk
+                     0, 0, 0, 0, 0, 0, 0, 1]; // end marker
+        if let Ok((scaler, header)) = QoiScaler::<2>::new_enlarged(&input, 2) {
+            assert!(header.width() == 4 && header.height() == 2);
+            if let Ok(QoiScalerProgress::Unfinished(scaler, buffer)) = scaler.process(&input, [0; 12]) {
+                assert!(is_identical(&buffer, &[1, 2, 3, 255, 1, 2, 3, 255, 4, 5, 6, 7]));
+                if let Ok(QoiScalerProgress::Unfinished(scaler, buffer)) = scaler.process(&input, [0; 12]) {
+                    assert!(is_identical(&buffer, &[4, 5, 6, 7, 1, 2, 3, 255, 1, 2, 3, 255])); // row repeated
+                    if let Ok(QoiScalerProgress::Finished(buffer, empty)) = scaler.process(&input, [0; 12]) {
+                        assert!(empty == 4 && is_identical(buffer.split_at(8).0, &[4, 5, 6, 7, 4, 5, 6, 7]));
+                    } else {
+                        unreachable!();
+                    }
+                } else {
+                    unreachable!();
+                }
+            } else {
+                unreachable!();
+            }
+        } else {
+            unreachable!();
+        }
+    }
+    #[test]
+    const fn bad_new() {
+        assert!(matches!(QoiScaler::<2>::new_shrunk(&INPUT, 0), Err(QoiError::InvalidScaleFactor(0))));
+        assert!(matches!(QoiScaler::<1>::new_shrunk(&INPUT, 2), Err(QoiError::ImageTooWide(2, 1))));
+        assert!(matches!(QoiScaler::<2>::new_enlarged(&INPUT, 2), Err(QoiError::ImageTooWide(3, 2))));
+        assert!(matches!(QoiScaler::<3>::new_enlarged(&INPUT, u32::MAX), Err(QoiError::InvalidScaleFactor(u32::MAX))));
+        if let Ok((scaler, _)) = QoiScaler::<3>::new_enlarged(&INPUT, 1) {
+            assert!(matches!(scaler.process(&INPUT, [0; 6]), Err(QoiError::IncorrectBufferSize(6))));
+        } else {
+            unreachable!();
+        }
+    }
+}
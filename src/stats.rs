@@ -0,0 +1,222 @@
+// indices into the chunk counters, one for each QOI chunk
+pub const RGB: usize = 0;
+pub const RGBA: usize = 1;
+pub const INDEX: usize = 2;
+pub const DIFF: usize = 3;
+pub const LUMA: usize = 4;
+pub const RUN: usize = 5;
+const CHUNK_SIZES: [u64; 6] = [4, 5, 1, 1, 2, 1]; // size in bytes of each chunk in the same order as the indices
+
+/// Statistics about the QOI data chunks processed by the [`QoiDecoder`](crate::QoiDecoder) or the
+/// [`QoiEncoder`](crate::QoiEncoder).
+///
+/// Collecting the chunk statistics is optional and must be enabled with
+/// [`QoiDecoder::with_stats`](crate::QoiDecoder::with_stats) or [`QoiEncoder::with_stats`](crate::QoiEncoder::with_stats).
+/// When not enabled every count will be `0` and [`QoiStats::alpha_used`] will be `false`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QoiStats {
+    chunks: [u64; 6], // amount of each chunk processed
+    longest_run: u64, // longest amount of pixels from consecutive run chunks
+    current_run: u64, // amount of pixels from the current consecutive run chunks
+    pixels: u64,      // amount of pixels processed, only counted when collecting
+    channels: u8,     // bytes per pixel of the uncompressed image
+    changed: u64,     // amount of pixels changed by the encoder before choosing chunks
+    alpha: bool,      // whether any pixel has alpha other than 255
+}
+
+impl QoiStats {
+    /// The amount of RGB chunks.
+    #[must_use]
+    pub const fn rgb_chunks(&self) -> u64 {
+        self.chunks[RGB]
+    }
+    /// The amount of RGBA chunks.
+    #[must_use]
+    pub const fn rgba_chunks(&self) -> u64 {
+        self.chunks[RGBA]
+    }
+    /// The amount of index chunks.
+    #[must_use]
+    pub const fn index_chunks(&self) -> u64 {
+        self.chunks[INDEX]
+    }
+    /// The amount of diff chunks.
+    #[must_use]
+    pub const fn diff_chunks(&self) -> u64 {
+        self.chunks[DIFF]
+    }
+    /// The amount of luma chunks.
+    #[must_use]
+    pub const fn luma_chunks(&self) -> u64 {
+        self.chunks[LUMA]
+    }
+    /// The amount of run chunks.
+    #[must_use]
+    pub const fn run_chunks(&self) -> u64 {
+        self.chunks[RUN]
+    }
+    /// The amount of bytes used by RGB chunks (`4` bytes each).
+    #[must_use]
+    pub const fn rgb_bytes(&self) -> u64 {
+        self.chunks[RGB] * CHUNK_SIZES[RGB]
+    }
+    /// The amount of bytes used by RGBA chunks (`5` bytes each).
+    #[must_use]
+    pub const fn rgba_bytes(&self) -> u64 {
+        self.chunks[RGBA] * CHUNK_SIZES[RGBA]
+    }
+    /// The amount of bytes used by index chunks (`1` byte each).
+    #[must_use]
+    pub const fn index_bytes(&self) -> u64 {
+        self.chunks[INDEX] * CHUNK_SIZES[INDEX]
+    }
+    /// The amount of bytes used by diff chunks (`1` byte each).
+    #[must_use]
+    pub const fn diff_bytes(&self) -> u64 {
+        self.chunks[DIFF] * CHUNK_SIZES[DIFF]
+    }
+    /// The amount of bytes used by luma chunks (`2` bytes each).
+    #[must_use]
+    pub const fn luma_bytes(&self) -> u64 {
+        self.chunks[LUMA] * CHUNK_SIZES[LUMA]
+    }
+    /// The amount of bytes used by run chunks (`1` byte each).
+    #[must_use]
+    pub const fn run_bytes(&self) -> u64 {
+        self.chunks[RUN] * CHUNK_SIZES[RUN]
+    }
+    /// The total amount of chunks.
+    #[must_use]
+    pub const fn total_chunks(&self) -> u64 {
+        self.chunks[RGB] + self.chunks[RGBA] + self.chunks[INDEX] + self.chunks[DIFF] + self.chunks[LUMA] + self.chunks[RUN]
+    }
+    /// The total amount of bytes used by chunks. This excludes the `14` byte header and `8` byte end marker.
+    #[must_use]
+    pub const fn total_chunk_bytes(&self) -> u64 {
+        self.rgb_bytes() + self.rgba_bytes() + self.index_bytes() + self.diff_bytes() + self.luma_bytes() + self.run_bytes()
+    }
+    /// The total amount of pixels processed.
+    #[must_use]
+    pub const fn pixel_amount(&self) -> u64 {
+        self.pixels
+    }
+    /// The longest amount of pixels encoded by consecutive run chunks.
+    ///
+    /// A single run chunk holds at most `62` pixels so longer runs are made up of multiple run chunks.
+    #[must_use]
+    pub const fn longest_run(&self) -> u64 {
+        self.longest_run
+    }
+    /// The amount of pixels not part of a run that were found in the previously seen pixels array followed by the
+    /// amount of pixels not part of a run.
+    ///
+    /// Every pixel that is not part of a run is looked up in the previously seen pixels array.
+    /// A successful lookup results in an index chunk.
+    /// Dividing the first value by the second gives the hit rate (`0` to `1`), both values are `0` when nothing was
+    /// looked up.
+    #[must_use]
+    pub const fn index_hit_rate(&self) -> (u64, u64) {
+        (self.chunks[INDEX], self.total_chunks() - self.chunks[RUN])
+    }
+    /// The size of the complete QOI image followed by the size of the uncompressed pixel data.
+    ///
+    /// The size of the QOI image includes the `14` byte header and `8` byte end marker.
+    /// The uncompressed size is based on `3` byte pixels (RGB) or `4` byte pixels (RGBA) depending on the channels.
+    /// Dividing the first value by the second gives the compression ratio where values below `1` mean the QOI image is
+    /// smaller than the uncompressed pixel data. Both values are `0` when no pixels were processed.
+    #[must_use]
+    pub const fn compression_ratio(&self) -> (u64, u64) {
+        if self.pixels == 0 {return (0, 0);}
+        (self.total_chunk_bytes() + 14 + 8, self.pixels * self.channels as u64)
+    }
+    /// The amount of pixels the [`QoiEncoder`](crate::QoiEncoder) changed before choosing chunks.
+    ///
+    /// Pixels are only changed when requested, such as with
+    /// [`QoiEncoder::with_transparent_pixels`](crate::QoiEncoder::with_transparent_pixels).
+    /// This is always `0` for the decoder.
+    #[must_use]
+    pub const fn changed_pixels(&self) -> u64 {
+        self.changed
+    }
+    /// Whether any pixel has an alpha value other than `255`.
+    ///
+    /// When `false` the image can be stored as `3` byte pixels (RGB) without losing any information which can be useful
+    /// when the header has already been written with a channels value of `4` (RGBA).
+    #[must_use]
+    pub const fn alpha_used(&self) -> bool {
+        self.alpha
+    }
+    pub(crate) const fn new(channels: u8) -> Self {
+        Self {chunks: [0; 6], longest_run: 0, current_run: 0, pixels: 0, channels, changed: 0, alpha: false}
+    }
+    // records the alpha value of a processed pixel. index chunks can also change alpha as the previously seen pixels
+    // start as 0, 0, 0, 0 so every pixel must be recorded
+    #[inline]
+    pub(crate) const fn record_alpha(mut self, alpha: u8) -> Self {
+        if alpha != 255 {self.alpha = true;}
+        self
+    }
+    // records a pixel changed by the encoder
+    #[inline]
+    pub(crate) const fn record_changed(mut self) -> Self {
+        self.changed += 1;
+        self
+    }
+    // records a processed chunk and the amount of pixels it represents
+    #[inline]
+    pub(crate) const fn record(mut self, chunk: usize, pixels: u64) -> Self {
+        self.chunks[chunk] += 1;
+        self.pixels += pixels;
+        if chunk == RUN {
+            self.current_run += pixels;
+            if self.current_run > self.longest_run {self.longest_run = self.current_run;}
+        } else {
+            self.current_run = 0;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QoiStats, DIFF, INDEX, LUMA, RGB, RGBA, RUN};
+    #[test]
+    const fn infallible_new() {
+        let stats = QoiStats::new(4);
+        assert!(stats.total_chunks() == 0);
+        assert!(stats.total_chunk_bytes() == 0);
+        assert!(stats.pixel_amount() == 0);
+        assert!(stats.longest_run() == 0);
+        assert!(matches!(stats.index_hit_rate(), (0, 0)));
+        assert!(matches!(stats.compression_ratio(), (0, 0)));
+        assert!(stats.changed_pixels() == 0);
+        assert!(stats.record_changed().changed_pixels() == 1);
+        assert!(!stats.alpha_used());
+        assert!(!stats.record_alpha(255).alpha_used());
+        assert!(stats.record_alpha(254).alpha_used());
+    }
+    #[test]
+    const fn infallible_record() {
+        let stats = QoiStats::new(3).record(RGB, 1)
+                                    .record(RUN, 62)
+                                    .record(RUN, 3)   // consecutive run of 65 pixels
+                                    .record(INDEX, 1)
+                                    .record(RUN, 10)
+                                    .record(DIFF, 1)
+                                    .record(LUMA, 1)
+                                    .record(RGBA, 1);
+        assert!(stats.rgb_chunks() == 1 && stats.rgb_bytes() == 4);
+        assert!(stats.rgba_chunks() == 1 && stats.rgba_bytes() == 5);
+        assert!(stats.index_chunks() == 1 && stats.index_bytes() == 1);
+        assert!(stats.diff_chunks() == 1 && stats.diff_bytes() == 1);
+        assert!(stats.luma_chunks() == 1 && stats.luma_bytes() == 2);
+        assert!(stats.run_chunks() == 3 && stats.run_bytes() == 3);
+        assert!(stats.total_chunks() == 8);
+        assert!(stats.total_chunk_bytes() == 16);
+        assert!(stats.pixel_amount() == 80);
+        assert!(stats.longest_run() == 65);
+        assert!(matches!(stats.index_hit_rate(), (1, 5)));      // 1 index chunk out of 5 lookups
+        assert!(matches!(stats.compression_ratio(), (38, 240))); // 16 chunk bytes + 22, 80 pixels of 3 bytes each
+    }
+}